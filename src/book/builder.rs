@@ -0,0 +1,189 @@
+//! Polyglot book builder: turns PGN games into a `.bin` book.
+//!
+//! Every main-line move up to `max_ply` is recorded under the Polyglot key
+//! of the position it was played from. A move scores 2 for each win and 1
+//! for each draw of the side that played it; its weight is the total
+//! score, scaled down to fit 16 bits. Moves seen in fewer than
+//! `min_games` games are left out.
+
+use super::polyglot::BookEntry;
+use super::zobrist::polyglot_hash;
+use crate::pgn::{Game, GameResult, PgnReader};
+use crate::types::{Board, Color, Move, Piece};
+use crate::types::castling::{castling_rook_square, is_castling};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Plies of each game added to the book by default
+pub const DEFAULT_MAX_PLY: usize = 40;
+/// Games a move needs by default to be kept
+pub const DEFAULT_MIN_GAMES: u32 = 3;
+
+/// Encode a move in Polyglot format: to file/rank in bits 0-5, from
+/// file/rank in bits 6-11, promotion piece in bits 12-14. Castling is
+/// written as the king taking its own rook.
+pub fn encode_move(board: &Board, m: Move) -> u16 {
+    let from = m.from();
+    let to = if is_castling(m) {
+        castling_rook_square(board, m).unwrap_or(m.to())
+    } else {
+        m.to()
+    };
+    let promotion = match m.flag().promotion_piece() {
+        Some(Piece::Knight) => 1,
+        Some(Piece::Bishop) => 2,
+        Some(Piece::Rook) => 3,
+        Some(Piece::Queen) => 4,
+        _ => 0,
+    };
+
+    to.file().index() as u16
+        | (to.rank().index() as u16) << 3
+        | (from.file().index() as u16) << 6
+        | (from.rank().index() as u16) << 9
+        | promotion << 12
+}
+
+/// Games and score of one move in one position
+#[derive(Debug, Clone, Copy, Default)]
+struct MoveStats {
+    games: u32,
+    score: u32,
+}
+
+/// Outcome of adding a PGN file
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PgnSummary {
+    /// Games added to the book
+    pub added: usize,
+    /// Games without a result (`*`)
+    pub unfinished: usize,
+    /// Games that didn't parse
+    pub errors: usize,
+}
+
+/// Accumulates games and writes them as a Polyglot book
+pub struct BookBuilder {
+    max_ply: usize,
+    min_games: u32,
+    moves: HashMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    pub fn new() -> Self {
+        Self {
+            max_ply: DEFAULT_MAX_PLY,
+            min_games: DEFAULT_MIN_GAMES,
+            moves: HashMap::new(),
+        }
+    }
+
+    /// Only add the first `max_ply` plies of each game
+    pub fn with_max_ply(mut self, max_ply: usize) -> Self {
+        self.max_ply = max_ply;
+        self
+    }
+
+    /// Leave out moves played in fewer than `min_games` games
+    pub fn with_min_games(mut self, min_games: u32) -> Self {
+        self.min_games = min_games;
+        self
+    }
+
+    /// Add the main line of a game. Returns `false` for a game without a
+    /// result, which can't weigh its moves and is skipped.
+    pub fn add_game(&mut self, game: &Game) -> Result<bool, String> {
+        let (white, black) = match game.result {
+            GameResult::WhiteWins => (2, 0),
+            GameResult::BlackWins => (0, 2),
+            GameResult::Draw => (1, 1),
+            GameResult::Unknown => return Ok(false),
+        };
+
+        for (board, m) in game.mainline_positions()?.into_iter().take(self.max_ply) {
+            let stats = self.moves.entry((polyglot_hash(&board), encode_move(&board, m))).or_default();
+            stats.games += 1;
+            stats.score += if board.turn() == Color::White { white } else { black };
+        }
+        Ok(true)
+    }
+
+    /// Add every game of a PGN file
+    pub fn add_pgn_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<PgnSummary> {
+        let mut summary = PgnSummary::default();
+        for game in PgnReader::open(path)? {
+            match game.and_then(|g| self.add_game(&g)) {
+                Ok(true) => summary.added += 1,
+                Ok(false) => summary.unfinished += 1,
+                Err(_) => summary.errors += 1,
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Book entries: moves with enough games, sorted by key and then by
+    /// weight, best first
+    pub fn entries(&self) -> Vec<BookEntry> {
+        let kept: Vec<_> = self.moves.iter()
+            .filter(|(_, stats)| stats.games >= self.min_games)
+            .collect();
+        let max_score = kept.iter().map(|(_, stats)| stats.score).max().unwrap_or(0);
+        let scale = max_score.div_ceil(u16::MAX as u32).max(1);
+
+        let mut entries: Vec<BookEntry> = kept.into_iter()
+            .map(|(&(key, raw_move), stats)| BookEntry {
+                key,
+                raw_move,
+                weight: (stats.score / scale) as u16,
+                learn: 0,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)).then(a.raw_move.cmp(&b.raw_move)));
+        entries
+    }
+
+    /// Write the book to `path`, returning the number of entries
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<usize> {
+        let entries = self.entries();
+        let mut out = BufWriter::new(File::create(path)?);
+        for entry in &entries {
+            out.write_all(&entry.to_bytes())?;
+        }
+        out.flush()?;
+        Ok(entries.len())
+    }
+}
+
+impl Default for BookBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_filter_scale_and_sort() {
+        let mut builder = BookBuilder::new().with_min_games(2);
+        builder.moves.insert((7, 1), MoveStats { games: 2, score: 1 });
+        builder.moves.insert((7, 2), MoveStats { games: 70_000, score: 140_000 });
+        builder.moves.insert((3, 3), MoveStats { games: 1, score: 2 });
+        builder.moves.insert((5, 4), MoveStats { games: 4, score: 0 });
+
+        let entries = builder.entries();
+        let keys: Vec<(u64, u16)> = entries.iter().map(|e| (e.key, e.raw_move)).collect();
+        assert_eq!(keys, vec![(5, 4), (7, 2), (7, 1)]);
+
+        // Scores are divided by 3 to fit 140000 into 16 bits
+        assert_eq!(entries[1].weight, 46_666);
+        assert_eq!(entries[2].weight, 0);
+
+        let bytes = entries[1].to_bytes();
+        assert_eq!(&bytes[..8], &7u64.to_be_bytes());
+        assert_eq!(&bytes[8..10], &2u16.to_be_bytes());
+    }
+}
@@ -0,0 +1,151 @@
+//! Book learning: reweighting book moves by the results of games.
+//!
+//! The engine remembers the book moves it played in a game. When the game
+//! ends, each of them is adjusted by the result from the engine's side: a
+//! win raises its weight by `WIN_BONUS_PERCENT`, a loss lowers it by
+//! `LOSS_MALUS_PERCENT` and a draw leaves it alone. Lines that keep losing
+//! are played less and less.
+//!
+//! The `learn` field keeps the record of each move: games played in the
+//! high 16 bits and points scored in the low 16 bits (2 per win, 1 per
+//! draw).
+
+use super::polyglot::BookEntry;
+use crate::pgn::GameResult;
+use crate::types::{Color, Score};
+
+/// Weight increase after a win, in percent (at least 1)
+pub const WIN_BONUS_PERCENT: u32 = 10;
+/// Weight decrease after a loss, in percent
+pub const LOSS_MALUS_PERCENT: u32 = 25;
+/// Final search score that counts as a win (or, negated, a loss) when the
+/// outcome of a game without a known result is guessed (opt-in)
+pub const DECISIVE_SCORE_CP: i32 = 200;
+
+/// Result of a game from the engine's side
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl Outcome {
+    /// Outcome for `side` of a game result (`None` if unfinished)
+    pub fn from_result(result: GameResult, side: Color) -> Option<Self> {
+        let white = match result {
+            GameResult::WhiteWins => Self::Win,
+            GameResult::BlackWins => Self::Loss,
+            GameResult::Draw => return Some(Self::Draw),
+            GameResult::Unknown => return None,
+        };
+        Some(match (white, side) {
+            (outcome, Color::White) => outcome,
+            (Self::Win, Color::Black) => Self::Loss,
+            (_, Color::Black) => Self::Win,
+        })
+    }
+
+    /// Outcome guessed from the engine's last search score
+    pub fn from_score(score: Score) -> Self {
+        if score >= Score::cp(DECISIVE_SCORE_CP) {
+            Self::Win
+        } else if score <= Score::cp(-DECISIVE_SCORE_CP) {
+            Self::Loss
+        } else {
+            Self::Draw
+        }
+    }
+
+    fn points(self) -> u32 {
+        match self {
+            Self::Win => 2,
+            Self::Draw => 1,
+            Self::Loss => 0,
+        }
+    }
+}
+
+/// Games recorded in a `learn` value
+pub fn learn_games(learn: u32) -> u32 {
+    learn >> 16
+}
+
+/// Points recorded in a `learn` value
+pub fn learn_points(learn: u32) -> u32 {
+    learn & 0xFFFF
+}
+
+/// Apply one game's outcome to an entry
+pub fn apply_outcome(entry: &mut BookEntry, outcome: Outcome) {
+    let weight = entry.weight as u32;
+    entry.weight = match outcome {
+        Outcome::Win => (weight + (weight * WIN_BONUS_PERCENT / 100).max(1)).min(u16::MAX as u32) as u16,
+        Outcome::Draw => entry.weight,
+        Outcome::Loss => (weight - weight * LOSS_MALUS_PERCENT / 100).min(weight.saturating_sub(1)) as u16,
+    };
+
+    // Stop recording once either counter would overflow
+    let games = learn_games(entry.learn) + 1;
+    let points = learn_points(entry.learn) + outcome.points();
+    if games <= 0xFFFF && points <= 0xFFFF {
+        entry.learn = games << 16 | points;
+    }
+}
+
+/// Apply a game's outcome to the entries of the book moves played in it,
+/// given as (Polyglot key, Polyglot move). Entries stay sorted by key and
+/// then by weight. Returns the number of entries updated.
+pub fn apply_learning(entries: &mut [BookEntry], played: &[(u64, u16)], outcome: Outcome) -> usize {
+    let mut updated = 0;
+    for entry in entries.iter_mut() {
+        if played.contains(&(entry.key, entry.raw_move)) {
+            apply_outcome(entry, outcome);
+            updated += 1;
+        }
+    }
+    if updated > 0 {
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)));
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, raw_move: u16, weight: u16) -> BookEntry {
+        BookEntry { key, raw_move, weight, learn: 0 }
+    }
+
+    #[test]
+    fn test_losing_lines_lose_weight() {
+        let mut entries = vec![entry(1, 10, 100), entry(1, 11, 90), entry(2, 12, 4)];
+
+        assert_eq!(apply_learning(&mut entries, &[(1, 10), (2, 12)], Outcome::Loss), 2);
+        // The 90-weight move now comes first for key 1
+        assert_eq!((entries[0].raw_move, entries[0].weight), (11, 90));
+        assert_eq!((entries[1].raw_move, entries[1].weight), (10, 75));
+        assert_eq!(entries[2].weight, 3);
+        assert_eq!((learn_games(entries[1].learn), learn_points(entries[1].learn)), (1, 0));
+
+        apply_learning(&mut entries, &[(1, 11)], Outcome::Win);
+        assert_eq!(entries[0].weight, 99);
+        assert_eq!((learn_games(entries[0].learn), learn_points(entries[0].learn)), (1, 2));
+
+        // Repeated losses drive a line to zero
+        for _ in 0..5 {
+            apply_learning(&mut entries, &[(2, 12)], Outcome::Loss);
+        }
+        assert_eq!(entries[2].weight, 0);
+    }
+
+    #[test]
+    fn test_outcome_from_result() {
+        assert_eq!(Outcome::from_result(GameResult::BlackWins, Color::Black), Some(Outcome::Win));
+        assert_eq!(Outcome::from_result(GameResult::BlackWins, Color::White), Some(Outcome::Loss));
+        assert_eq!(Outcome::from_result(GameResult::Draw, Color::Black), Some(Outcome::Draw));
+        assert_eq!(Outcome::from_result(GameResult::Unknown, Color::White), None);
+        assert_eq!(Outcome::from_score(Score::cp(-350)), Outcome::Loss);
+    }
+}
@@ -2,23 +2,32 @@
 //!
 //! This module provides support for Polyglot format opening books (.bin files).
 //! Polyglot is a widely used standard format for chess opening books.
+//! `BookBuilder` makes such books from PGN game collections, and
+//! `PolyglotBook::learn` reweights a book by the results of the games
+//! played from it.
 //!
 //! # Usage
 //!
 //! ```ignore
 //! use chessinrust::book::PolyglotBook;
+//! use chessinrust::types::Prng;
 //!
 //! let book = PolyglotBook::load("Openings.bin")?;
 //! let board = Board::default();
 //!
 //! // Get a weighted random move from the book
-//! if let Some(m) = book.probe_move(&board) {
+//! let mut rng = Prng::new(1);
+//! if let Some(m) = book.probe_move(&board, &mut rng) {
 //!     println!("Book move: {}", m);
 //! }
 //! ```
 
+mod builder;
+mod learning;
 mod polyglot;
 mod zobrist;
 
+pub use builder::{BookBuilder, PgnSummary, encode_move, DEFAULT_MAX_PLY, DEFAULT_MIN_GAMES};
+pub use learning::{Outcome, learn_games, learn_points};
 pub use polyglot::{PolyglotBook, BookEntry};
 pub use zobrist::polyglot_hash;
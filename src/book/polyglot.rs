@@ -1,11 +1,13 @@
 //! Polyglot opening book format reader.
 
+use super::learning::{apply_learning, Outcome};
 use super::zobrist::polyglot_hash;
-use crate::types::{Board, Move, Piece};
+use crate::types::{Board, Move, Piece, Prng};
+use crate::types::castling;
 use movegen::{Square, File, Rank};
 use std::fs::File as FsFile;
-use std::io::{self, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 
 const ENTRY_SIZE: usize = 16;
 
@@ -28,6 +30,16 @@ impl BookEntry {
         }
     }
 
+    /// Big-endian file representation, as read by `from_bytes`
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.raw_move.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+
     pub fn decode_move(&self) -> (Square, Square, Option<Piece>) {
         let to_file = (self.raw_move & 0x7) as u8;
         let to_rank = ((self.raw_move >> 3) & 0x7) as u8;
@@ -47,10 +59,14 @@ impl BookEntry {
 
     pub fn to_chess_move(&self, board: &Board) -> Option<Move> {
         let (from, to, promo) = self.decode_move();
-        let actual_to = self.adjust_castling_move(board, from, to);
-        
+
+        // Polyglot writes castling as the king taking its own rook
+        if let Some(m) = castling::find_castling_move(board, from, to) {
+            return Some(m);
+        }
+
         for m in board.generate_moves().iter() {
-            if m.from() == from && m.to() == actual_to {
+            if m.from() == from && m.to() == to {
                 if promo.is_some() {
                     if m.flag().promotion_piece() == promo { return Some(m); }
                 } else if m.flag().promotion_piece().is_none() {
@@ -60,21 +76,12 @@ impl BookEntry {
         }
         None
     }
-
-    fn adjust_castling_move(&self, board: &Board, from: Square, to: Square) -> Square {
-        if let Some((piece, _)) = board.piece_at(from) {
-            if piece == Piece::King && from.file() == File::E {
-                if to.file() == File::H { return Square::from_file_rank(File::G, to.rank()); }
-                if to.file() == File::A { return Square::from_file_rank(File::C, to.rank()); }
-            }
-        }
-        to
-    }
 }
 
 pub struct PolyglotBook {
     data: BookData,
     entry_count: usize,
+    path: PathBuf,
     pub desc: String,
 }
 
@@ -95,40 +102,91 @@ impl PolyglotBook {
         let desc = path.to_string_lossy().to_string();
         
         if file_size <= 50 * 1024 * 1024 {
-            let mut data = vec![0u8; file_size as usize];
-            file.read_exact(&mut data)?;
-            let entries = data.chunks_exact(ENTRY_SIZE)
-                .map(|c| BookEntry::from_bytes(c.try_into().unwrap())).collect();
-            Ok(Self { data: BookData::Memory(entries), entry_count, desc })
+            let entries = read_entries(&mut file, file_size)?;
+            Ok(Self { data: BookData::Memory(entries), entry_count, path: path.to_path_buf(), desc })
         } else {
-            Ok(Self { data: BookData::File { path: desc.clone() }, entry_count, desc })
+            Ok(Self { data: BookData::File { path: desc.clone() }, entry_count, path: path.to_path_buf(), desc })
         }
     }
 
+    /// Apply a game's outcome to the book moves played in it, given as
+    /// (Polyglot key, Polyglot move), and write the book back to disk.
+    /// Returns the number of entries updated.
+    ///
+    /// The whole book is rewritten: one kept on disk (over 50 MB) is read
+    /// fully into memory for each update.
+    pub fn learn(&mut self, played: &[(u64, u16)], outcome: Outcome) -> io::Result<usize> {
+        let mut loaded;
+        let entries = match &mut self.data {
+            BookData::Memory(entries) => entries,
+            BookData::File { path } => {
+                let mut file = FsFile::open(&*path)?;
+                let file_size = file.metadata()?.len();
+                loaded = read_entries(&mut file, file_size)?;
+                &mut loaded
+            }
+        };
+
+        let updated = apply_learning(entries, played, outcome);
+        if updated > 0 {
+            // Write a copy first so a failed write can't truncate the book
+            let tmp = self.path.with_extension("bin.tmp");
+            let mut out = BufWriter::new(FsFile::create(&tmp)?);
+            for entry in entries.iter() {
+                out.write_all(&entry.to_bytes())?;
+            }
+            out.flush()?;
+            drop(out);
+            std::fs::rename(&tmp, &self.path)?;
+        }
+        Ok(updated)
+    }
+
     pub fn probe(&self, board: &Board) -> Vec<BookEntry> {
         self.find_entries(polyglot_hash(board))
     }
 
-    pub fn probe_move(&self, board: &Board) -> Option<Move> {
-        let entries = self.probe(board);
-        if entries.is_empty() { return None; }
-        let total: u32 = entries.iter().map(|e| e.weight as u32).sum();
-        if total == 0 { return entries[0].to_chess_move(board); }
-        
-        let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
-            .map(|d| d.as_nanos() as u64).unwrap_or(12345);
-        let random = seed.wrapping_mul(6364136223846793005).wrapping_add(1) % total as u64;
-        
-        let mut cum = 0u64;
-        for e in &entries {
-            cum += e.weight as u64;
-            if random < cum { return e.to_chess_move(board); }
-        }
-        entries[0].to_chess_move(board)
+    /// Weighted random book move: each move is picked in proportion to its
+    /// weight, and zero-weight moves never are
+    pub fn probe_move(&self, board: &Board, rng: &mut Prng) -> Option<Move> {
+        Self::pick_weighted(&self.candidates(board, 1), rng)
     }
 
+    /// Book move with the highest weight (zero-weight moves are never played)
     pub fn probe_best_move(&self, board: &Board) -> Option<Move> {
-        self.probe(board).iter().max_by_key(|e| e.weight).and_then(|e| e.to_chess_move(board))
+        Self::pick_best(&self.candidates(board, 1))
+    }
+
+    /// Legal book moves of the position with their weights, leaving out
+    /// moves weighing less than `min_weight` (and at least 1). Entries are
+    /// kept in book order, so the heaviest move comes first.
+    pub fn candidates(&self, board: &Board, min_weight: u16) -> Vec<(Move, u16)> {
+        let mut moves: Vec<(Move, u16)> = Vec::new();
+        for e in self.probe(board) {
+            if e.weight < min_weight.max(1) { continue; }
+            if let Some(m) = e.to_chess_move(board) {
+                if !moves.iter().any(|&(n, _)| n == m) { moves.push((m, e.weight)); }
+            }
+        }
+        moves
+    }
+
+    /// Pick a move in proportion to its weight
+    pub fn pick_weighted(moves: &[(Move, u16)], rng: &mut Prng) -> Option<Move> {
+        let total: u64 = moves.iter().map(|&(_, w)| w as u64).sum();
+        if total == 0 { return None; }
+
+        let mut target = rng.below(total);
+        for &(m, w) in moves {
+            if target < w as u64 { return Some(m); }
+            target -= w as u64;
+        }
+        None
+    }
+
+    /// Pick the heaviest move (the first one on a tie)
+    pub fn pick_best(moves: &[(Move, u16)]) -> Option<Move> {
+        moves.iter().rev().filter(|&&(_, w)| w > 0).max_by_key(|&&(_, w)| w).map(|&(m, _)| m)
     }
 
     fn find_entries(&self, key: u64) -> Vec<BookEntry> {
@@ -173,3 +231,28 @@ impl PolyglotBook {
     pub fn len(&self) -> usize { self.entry_count }
     pub fn is_empty(&self) -> bool { self.entry_count == 0 }
 }
+
+fn read_entries(file: &mut FsFile, file_size: u64) -> io::Result<Vec<BookEntry>> {
+    let mut data = vec![0u8; file_size as usize];
+    file.read_exact(&mut data)?;
+    Ok(data.chunks_exact(ENTRY_SIZE)
+        .map(|c| BookEntry::from_bytes(c.try_into().unwrap())).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::MoveFlag;
+
+    #[test]
+    fn test_pick_skips_zero_weights() {
+        let moves = [(Move::NULL, 0), (Move::new(Square::E2, Square::E4, MoveFlag::DoublePawnPush), 3)];
+        let mut rng = Prng::new(7);
+        for _ in 0..50 {
+            assert_eq!(PolyglotBook::pick_weighted(&moves, &mut rng), Some(moves[1].0));
+        }
+        assert_eq!(PolyglotBook::pick_best(&moves), Some(moves[1].0));
+        assert_eq!(PolyglotBook::pick_weighted(&moves[..1], &mut rng), None);
+        assert_eq!(PolyglotBook::pick_best(&moves[..1]), None);
+    }
+}
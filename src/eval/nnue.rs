@@ -3,6 +3,7 @@
 //! Uses ferrum-nnue with Aurora architecture (768→256×2→1) for fast evaluation.
 
 use crate::types::{Board, Score, ToNnue, Move, Piece, Color, MoveFlag};
+use crate::types::castling;
 use nnue::aurora::{load_model as aurora_load, AuroraModel, AuroraState};
 use std::sync::Arc;
 use movegen::Square;
@@ -70,10 +71,26 @@ pub fn update_state_for_move(
         Some((p, c)) => (p, c),
         None => return false,
     };
-    let captured = board.piece_at(to).map(|(p, _)| p);
-
     let nnue_piece = moving_piece.to_nnue();
     let nnue_color = moving_color.to_nnue();
+
+    // Handle castling: the king and rook both move. In Chess960 the rook can
+    // start on any file and the move may be encoded as king-takes-rook, so
+    // `to` is neither the king's destination nor a capture.
+    if castling::is_castling(mv) {
+        let Some(rook_from) = castling::castling_rook_square(board, mv) else {
+            return false;
+        };
+        let (king_to, rook_to) = castling::castling_destinations(mv);
+
+        state.sub(nnue_piece, nnue_color, from.to_nnue());
+        state.sub(nnue::Piece::Rook, nnue_color, rook_from.to_nnue());
+        state.add(nnue_piece, nnue_color, king_to.to_nnue());
+        state.add(nnue::Piece::Rook, nnue_color, rook_to.to_nnue());
+        return true;
+    }
+
+    let captured = board.piece_at(to).map(|(p, _)| p);
     let from_sq = from.to_nnue();
     let to_sq = to.to_nnue();
 
@@ -109,35 +126,6 @@ pub fn update_state_for_move(
     // Add piece to new square
     state.add(final_piece, nnue_color, to_sq);
 
-    // Handle castling: rook also moves
-    let mv_flag = mv.flag();
-    let is_castling = mv_flag == MoveFlag::KingCastle || mv_flag == MoveFlag::QueenCastle;
-    
-    if is_castling {
-        let nnue_rook_color = moving_color.to_nnue();
-        let (rook_from, rook_to) = if mv_flag == MoveFlag::KingCastle {
-            // King-side castling
-            let rank = from.rank();
-            (
-                Square::from_file_rank(movegen::File::H, rank),
-                Square::from_file_rank(movegen::File::F, rank)
-            )
-        } else {
-            // Queen-side castling
-            let rank = from.rank();
-            (
-                Square::from_file_rank(movegen::File::A, rank),
-                Square::from_file_rank(movegen::File::D, rank)
-            )
-        };
-        
-        let rook_from_nnue = rook_from.to_nnue();
-        let rook_to_nnue = rook_to.to_nnue();
-        
-        state.sub(nnue::Piece::Rook, nnue_rook_color, rook_from_nnue);
-        state.add(nnue::Piece::Rook, nnue_rook_color, rook_to_nnue);
-    }
-
     true
 }
 
@@ -3,4 +3,7 @@ pub mod engine;
 pub mod search;
 pub mod eval;
 pub mod uci;
+pub mod pgn;
 pub mod book;
+pub mod syzygy;
+pub mod tools;
@@ -2,5 +2,13 @@ use chessinrust::uci::UciHandler;
 
 fn main() {
     let mut handler = UciHandler::new();
+
+    // `chessinrust bench [depth] [threads] [hash]` runs the benchmark and exits
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("bench") {
+        handler.handle_input(&args.join(" "));
+        return;
+    }
+
     handler.run();
 }
@@ -0,0 +1,151 @@
+//! PGN (Portable Game Notation) game records.
+//!
+//! A PGN file holds games as tag pairs (`[Event "..."]`) followed by
+//! movetext: SAN moves with move numbers, `{comments}`, NAGs (`$1` or
+//! `!`/`?` suffixes), `(variations)` and a result marker.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! use chessinrust::pgn::PgnReader;
+//!
+//! for game in PgnReader::open("games.pgn")? {
+//!     let game = game?;
+//!     for (board, m) in game.mainline_positions()? {
+//!         // `m` was played from `board`
+//!     }
+//!     println!("{}", game);
+//! }
+//! ```
+
+mod reader;
+mod writer;
+
+pub use reader::PgnReader;
+
+use crate::types::{Board, Move};
+use std::fmt;
+
+/// Final result of a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// Unfinished or unknown (`*`)
+    #[default]
+    Unknown,
+}
+
+impl GameResult {
+    /// Parse a result marker ("1-0", "0-1", "1/2-1/2", "*")
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "1-0" => Some(Self::WhiteWins),
+            "0-1" => Some(Self::BlackWins),
+            "1/2-1/2" => Some(Self::Draw),
+            "*" => Some(Self::Unknown),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::WhiteWins => "1-0",
+            Self::BlackWins => "0-1",
+            Self::Draw => "1/2-1/2",
+            Self::Unknown => "*",
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A played move with its annotations
+#[derive(Debug, Clone)]
+pub struct MoveNode {
+    pub mv: Move,
+    /// Numeric annotation glyphs (`$1` = "!", `$2` = "?", ...)
+    pub nags: Vec<u8>,
+    /// Comment after the move
+    pub comment: Option<String>,
+    /// Alternatives to this move, each played from the same position
+    pub variations: Vec<Variation>,
+}
+
+impl MoveNode {
+    pub fn new(mv: Move) -> Self {
+        Self { mv, nags: Vec::new(), comment: None, variations: Vec::new() }
+    }
+}
+
+/// A sequence of moves (the main line or a variation)
+#[derive(Debug, Clone, Default)]
+pub struct Variation {
+    /// Comment before the first move
+    pub comment: Option<String>,
+    pub moves: Vec<MoveNode>,
+}
+
+/// One game: tags, moves and result
+#[derive(Debug, Clone, Default)]
+pub struct Game {
+    /// Tag pairs in file order
+    pub tags: Vec<(String, String)>,
+    pub mainline: Variation,
+    pub result: GameResult,
+}
+
+impl Game {
+    /// Parse a single game. Trailing text after the game is ignored.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        reader::parse_game(text)
+    }
+
+    /// Value of a tag
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Set a tag, replacing an existing one of the same name
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Starting position: the `FEN` tag, or the standard position
+    pub fn start_board(&self) -> Result<Board, String> {
+        match self.tag("FEN") {
+            Some(fen) => Board::from_fen(fen).map_err(|e| format!("invalid FEN tag: {}", e)),
+            None => Ok(Board::default()),
+        }
+    }
+
+    /// Append a move to the main line
+    pub fn push(&mut self, mv: Move) {
+        self.mainline.moves.push(MoveNode::new(mv));
+    }
+
+    /// Main-line moves, each with the position it was played from
+    pub fn mainline_positions(&self) -> Result<Vec<(Board, Move)>, String> {
+        let mut board = self.start_board()?;
+        let mut out = Vec::with_capacity(self.mainline.moves.len());
+        for node in &self.mainline.moves {
+            out.push((board, node.mv));
+            board = board.make_move_new(node.mv);
+        }
+        Ok(out)
+    }
+
+    /// Position at the end of the main line
+    pub fn final_board(&self) -> Result<Board, String> {
+        let board = self.start_board()?;
+        Ok(self.mainline.moves.iter().fold(board, |b, node| b.make_move_new(node.mv)))
+    }
+}
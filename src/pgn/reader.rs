@@ -0,0 +1,413 @@
+//! PGN parsing: splitting a file into games and games into tags and moves.
+
+use super::{Game, GameResult, MoveNode, Variation};
+use crate::types::Board;
+use crate::uci::parse_san;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// Lexical element of a PGN game
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// `[Name "value"]`
+    Tag(String, String),
+    /// SAN move, with the NAG of a `!`/`?` suffix
+    Move(String, Option<u8>),
+    /// `$n`
+    Nag(u8),
+    /// `{...}` or `;...` to the end of the line
+    Comment(String),
+    /// `(`: start of a variation
+    Open,
+    /// `)`: end of a variation
+    Close,
+    Result(GameResult),
+}
+
+/// NAG of a move suffix annotation
+fn suffix_nag(suffix: &str) -> Option<u8> {
+    match suffix {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None,
+    }
+}
+
+/// Split game text into tokens. Move numbers are dropped.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line_start = true;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => {
+                line_start = true;
+                i += 1;
+                continue;
+            }
+            // Escape mechanism: the whole line is ignored
+            '%' if line_start => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            c if c.is_whitespace() || c == '\u{feff}' => i += 1,
+            ';' => {
+                let start = i + 1;
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                tokens.push(Token::Comment(chars[start..i].iter().collect::<String>().trim().to_string()));
+            }
+            '{' => {
+                let start = i + 1;
+                let end = (start..chars.len()).find(|&j| chars[j] == '}').ok_or("unterminated comment")?;
+                let comment: String = chars[start..end].iter().collect();
+                tokens.push(Token::Comment(comment.split_whitespace().collect::<Vec<_>>().join(" ")));
+                i = end + 1;
+            }
+            '[' => {
+                // The tag ends at the first ']' outside its quoted value
+                let (mut end, mut quoted, mut escaped) = (i + 1, false, false);
+                while end < chars.len() && chars[end] != '\n' && (quoted || chars[end] != ']') {
+                    match chars[end] {
+                        '\\' if quoted && !escaped => escaped = true,
+                        '"' if !escaped => quoted = !quoted,
+                        _ => escaped = false,
+                    }
+                    end += 1;
+                }
+                let tag: String = chars[i..(end + 1).min(chars.len())].iter().collect();
+                tokens.push(parse_tag(&tag).ok_or_else(|| format!("bad tag: {}", tag.trim()))?);
+                i = end + 1;
+            }
+            '(' => {
+                tokens.push(Token::Open);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::Close);
+                i += 1;
+            }
+            '$' => {
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+                let nag: String = chars[start..i].iter().collect();
+                tokens.push(Token::Nag(nag.parse().map_err(|_| format!("bad NAG ${}", nag))?));
+            }
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"{}()[];$".contains(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                if let Some(token) = classify_word(&word)? {
+                    tokens.push(token);
+                }
+            }
+        }
+        line_start = false;
+    }
+
+    Ok(tokens)
+}
+
+/// Parse a `[Name "value"]` tag
+fn parse_tag(tag: &str) -> Option<Token> {
+    let inner = tag.trim().strip_prefix('[')?.trim_start();
+    let (name, rest) = inner.split_at(inner.find(|c: char| c.is_whitespace() || c == '"')?);
+    let quoted = rest.trim_start().strip_prefix('"')?;
+
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    loop {
+        match chars.next()? {
+            '\\' => value.push(chars.next()?),
+            '"' => break,
+            c => value.push(c),
+        }
+    }
+    if name.is_empty() {
+        return None;
+    }
+    Some(Token::Tag(name.to_string(), value))
+}
+
+/// Turn a movetext word into a token: a result, a move (possibly glued to
+/// its move number, as in "12.Nf3"), or nothing for a bare move number
+fn classify_word(word: &str) -> Result<Option<Token>, String> {
+    if let Some(result) = GameResult::parse(word) {
+        return Ok(Some(Token::Result(result)));
+    }
+
+    // Strip a leading move number ("12." / "12...")
+    let digits = word.len() - word.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let word = if digits > 0 && word[digits..].starts_with('.') {
+        word[digits..].trim_start_matches('.')
+    } else {
+        word
+    };
+    if word.is_empty() || word.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let san = word.trim_end_matches(['!', '?']);
+    let suffix = &word[san.len()..];
+    if san.is_empty() {
+        return Err(format!("unexpected '{}'", word));
+    }
+    Ok(Some(Token::Move(san.to_string(), suffix_nag(suffix))))
+}
+
+/// Add a comment, joining it to one already there
+fn append_comment(slot: &mut Option<String>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match slot {
+        Some(existing) => {
+            existing.push(' ');
+            existing.push_str(text);
+        }
+        None => *slot = Some(text.to_string()),
+    }
+}
+
+/// Parse moves from `board` until the end of the variation (`depth > 0`)
+/// or the game. Returns the line and the result marker, if one ended it.
+fn parse_variation(
+    tokens: &[Token],
+    pos: &mut usize,
+    mut board: Board,
+    depth: usize,
+) -> Result<(Variation, Option<GameResult>), String> {
+    let mut line = Variation::default();
+    // Position before the last move, where its variations start
+    let mut before = board;
+
+    while let Some(token) = tokens.get(*pos) {
+        *pos += 1;
+        match token {
+            Token::Move(san, nag) => {
+                let mv = parse_san(&board, san).ok_or_else(|| format!("illegal or ambiguous move {}", san))?;
+                let mut node = MoveNode::new(mv);
+                node.nags.extend(*nag);
+                line.moves.push(node);
+                before = board;
+                board = board.make_move_new(mv);
+            }
+            Token::Nag(nag) => {
+                if let Some(node) = line.moves.last_mut() {
+                    node.nags.push(*nag);
+                }
+            }
+            Token::Comment(text) => match line.moves.last_mut() {
+                Some(node) => append_comment(&mut node.comment, text),
+                None => append_comment(&mut line.comment, text),
+            },
+            Token::Open => {
+                let node = line.moves.last_mut().ok_or("variation before any move")?;
+                let (variation, _) = parse_variation(tokens, pos, before, depth + 1)?;
+                node.variations.push(variation);
+            }
+            Token::Close if depth > 0 => return Ok((line, None)),
+            Token::Close => return Err("unmatched ')'".to_string()),
+            Token::Result(_) if depth > 0 => return Err("result inside a variation".to_string()),
+            Token::Result(result) => return Ok((line, Some(*result))),
+            // The next game's tags: this game had no result marker
+            Token::Tag(..) if depth == 0 => {
+                *pos -= 1;
+                break;
+            }
+            Token::Tag(..) => return Err("tag inside a variation".to_string()),
+        }
+    }
+
+    if depth > 0 {
+        return Err("unterminated variation".to_string());
+    }
+    Ok((line, None))
+}
+
+/// Parse one game: its tags, then its movetext
+pub(super) fn parse_game(text: &str) -> Result<Game, String> {
+    let tokens = tokenize(text)?;
+    let mut game = Game::default();
+
+    let mut pos = 0;
+    while let Some(Token::Tag(name, value)) = tokens.get(pos) {
+        game.tags.push((name.clone(), value.clone()));
+        pos += 1;
+    }
+
+    let board = game.start_board()?;
+    let (mainline, result) = parse_variation(&tokens, &mut pos, board, 0)?;
+    game.mainline = mainline;
+    game.result = result
+        .or_else(|| game.tag("Result").and_then(GameResult::parse))
+        .unwrap_or_default();
+    Ok(game)
+}
+
+/// Track whether a line ends inside a `{...}` comment
+fn ends_in_comment(line: &str, mut in_comment: bool) -> bool {
+    for c in line.chars() {
+        match c {
+            '}' if in_comment => in_comment = false,
+            '{' if !in_comment => in_comment = true,
+            ';' if !in_comment => break,
+            _ => {}
+        }
+    }
+    in_comment
+}
+
+/// Reads the games of a PGN file one at a time
+pub struct PgnReader<R> {
+    lines: io::Lines<R>,
+    /// First tag line of the next game, read while finishing the previous one
+    pending: Option<String>,
+}
+
+impl PgnReader<BufReader<File>> {
+    /// Open a PGN file
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { lines: reader.lines(), pending: None }
+    }
+
+    /// Text of the next game, `None` at the end of the input. A game ends
+    /// where a tag line follows its movetext.
+    fn next_game_text(&mut self) -> Option<io::Result<String>> {
+        let mut text = String::new();
+        if let Some(line) = self.pending.take() {
+            text.push_str(&line);
+            text.push('\n');
+        }
+
+        let mut in_movetext = false;
+        let mut in_comment = false;
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            let trimmed = line.trim_start();
+            if !in_comment && trimmed.starts_with('[') {
+                if in_movetext {
+                    self.pending = Some(line);
+                    break;
+                }
+            } else {
+                if !in_comment && !trimmed.is_empty() && !trimmed.starts_with('%') {
+                    in_movetext = true;
+                }
+                in_comment = ends_in_comment(&line, in_comment);
+            }
+            text.push_str(&line);
+            text.push('\n');
+        }
+
+        if text.trim().is_empty() {
+            None
+        } else {
+            Some(Ok(text))
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.next_game_text()?;
+        Some(text.map_err(|e| e.to_string()).and_then(|text| parse_game(&text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize(
+            "[Event \"Casual \\\"game\\\"\"]\n\
+             % escaped line\n\
+             1. e4 e5!? 2.Nf3 $1 {A comment\n over lines} (2. f4 exf4) 2... Nc6 ; rest of line\n\
+             1-0",
+        )
+        .unwrap();
+
+        assert_eq!(tokens, vec![
+            Token::Tag("Event".to_string(), "Casual \"game\"".to_string()),
+            Token::Move("e4".to_string(), None),
+            Token::Move("e5".to_string(), Some(5)),
+            Token::Move("Nf3".to_string(), None),
+            Token::Nag(1),
+            Token::Comment("A comment over lines".to_string()),
+            Token::Open,
+            Token::Move("f4".to_string(), None),
+            Token::Move("exf4".to_string(), None),
+            Token::Close,
+            Token::Move("Nc6".to_string(), None),
+            Token::Comment("rest of line".to_string()),
+            Token::Result(GameResult::WhiteWins),
+        ]);
+
+        assert!(tokenize("1. e4 {unterminated").is_err());
+    }
+
+    #[test]
+    fn test_split_games() {
+        let pgn = "[Event \"A\"]\n[Result \"1-0\"]\n\n1. e4 {[not a tag]\n[still a comment]} 1-0\n\n\
+                   [Event \"B\"]\n\n1. d4 *\n";
+        let mut reader = PgnReader::new(pgn.as_bytes());
+
+        let first = reader.next_game_text().unwrap().unwrap();
+        assert!(first.starts_with("[Event \"A\"]") && first.contains("[still a comment]} 1-0"));
+        let second = reader.next_game_text().unwrap().unwrap();
+        assert!(second.starts_with("[Event \"B\"]") && second.contains("1. d4 *"));
+        assert!(reader.next_game_text().is_none());
+    }
+
+    #[test]
+    fn test_parse_game_with_variations() {
+        let game = Game::parse(
+            "[White \"A\"]\n[Black \"B\"]\n\n\
+             {Start} 1. e4 e5 2. Nf3 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 $1 3. Bb5 a6 1/2-1/2",
+        )
+        .unwrap();
+
+        assert_eq!(game.tag("White"), Some("A"));
+        assert_eq!(game.result, GameResult::Draw);
+        assert_eq!(game.mainline.comment.as_deref(), Some("Start"));
+        assert_eq!(game.mainline.moves.len(), 6);
+
+        let nf3 = &game.mainline.moves[2];
+        assert_eq!(nf3.variations.len(), 1);
+        assert_eq!(nf3.variations[0].moves.len(), 3);
+        assert_eq!(nf3.variations[0].moves[1].variations[0].moves.len(), 1);
+        assert_eq!(game.mainline.moves[3].nags, vec![1]);
+
+        assert!(Game::parse("1. e4 e4").is_err());
+        assert!(Game::parse("1. e4 (1. d4").is_err());
+    }
+}
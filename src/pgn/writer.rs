@@ -0,0 +1,151 @@
+//! PGN export: tags first (Seven Tag Roster order), then SAN movetext
+//! wrapped below 80 columns.
+
+use super::{Game, Variation};
+use crate::types::{Board, Color};
+use crate::uci::to_san;
+use std::fmt;
+
+/// Tags every exported game carries, in this order
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// Longest movetext line
+const LINE_WIDTH: usize = 79;
+
+/// Escape a tag value
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Comment text, with braces that would end it early removed
+fn comment_token(text: &str) -> String {
+    format!("{{{}}}", text.replace('}', ""))
+}
+
+/// Movetext tokens of a line played from `board`, whose first move has
+/// move number `number`
+fn variation_tokens(out: &mut Vec<String>, line: &Variation, mut board: Board, mut number: u32) {
+    // Black moves need their number after anything interrupting the line
+    let mut need_number = true;
+    if let Some(comment) = &line.comment {
+        out.push(comment_token(comment));
+    }
+
+    for node in &line.moves {
+        let white = board.turn() == Color::White;
+        if white {
+            out.push(format!("{}.", number));
+        } else if need_number {
+            out.push(format!("{}...", number));
+        }
+        out.push(to_san(&board, node.mv));
+        out.extend(node.nags.iter().map(|nag| format!("${}", nag)));
+        need_number = false;
+
+        if let Some(comment) = &node.comment {
+            out.push(comment_token(comment));
+            need_number = true;
+        }
+        for variation in &node.variations {
+            out.push("(".to_string());
+            variation_tokens(out, variation, board, number);
+            out.push(")".to_string());
+            need_number = true;
+        }
+
+        board = board.make_move_new(node.mv);
+        if !white {
+            number += 1;
+        }
+    }
+}
+
+/// Join tokens into lines of at most `LINE_WIDTH` columns, without spaces
+/// inside variation parentheses
+fn wrap(tokens: &[String]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut prev: Option<&str> = None;
+
+    for token in tokens {
+        let glued = prev == Some("(") || token == ")";
+        let sep = usize::from(!line.is_empty() && !glued);
+        if !line.is_empty() && line.len() + sep + token.len() > LINE_WIDTH {
+            lines.push(std::mem::take(&mut line));
+        } else if sep == 1 {
+            line.push(' ');
+        }
+        line.push_str(token);
+        prev = Some(token);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+impl Game {
+    /// Full move number of the first move, from the `FEN` tag
+    fn first_move_number(&self) -> u32 {
+        self.tag("FEN")
+            .and_then(|fen| fen.split_whitespace().nth(5))
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(1)
+    }
+}
+
+/// Writes the game in PGN export format. A game whose `FEN` tag doesn't
+/// parse is written without moves.
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in SEVEN_TAG_ROSTER {
+            let value = match name {
+                "Result" => self.result.as_str(),
+                _ => self.tag(name).unwrap_or("?"),
+            };
+            writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+            }
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        if let Ok(board) = self.start_board() {
+            variation_tokens(&mut tokens, &self.mainline, board, self.first_move_number());
+        }
+        tokens.push(self.result.to_string());
+        for line in wrap(&tokens) {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::GameResult;
+
+    #[test]
+    fn test_wrap_glues_parentheses() {
+        let tokens: Vec<String> = ["1.", "e4", "(", "1.", "d4", ")", "1...", "e5", "*"]
+            .iter().map(|s| s.to_string()).collect();
+        assert_eq!(wrap(&tokens), vec!["1. e4 (1. d4) 1... e5 *"]);
+
+        let long: Vec<String> = (0..40).map(|_| "Nf3".to_string()).collect();
+        assert!(wrap(&long).iter().all(|line| line.len() <= LINE_WIDTH));
+    }
+
+    #[test]
+    fn test_write_round_trip() {
+        let text = "[Event \"Test\"]\n[Site \"?\"]\n[Date \"2024.01.01\"]\n[Round \"1\"]\n\
+                    [White \"A\"]\n[Black \"B\"]\n[Result \"0-1\"]\n[ECO \"C20\"]\n\n\
+                    1. e4 e5 {Symmetric} 2. Nf3 (2. f4 exf4) 2... Nc6 $1 0-1\n";
+        let game = Game::parse(text).unwrap();
+        assert_eq!(game.result, GameResult::BlackWins);
+        assert_eq!(game.to_string(), text);
+    }
+}
@@ -0,0 +1,195 @@
+//! Draw detection: repetitions, the fifty-move rule and insufficient material.
+//!
+//! `PositionHistory` holds the game's positions followed by the current
+//! search path. The search pushes each child position before searching it,
+//! so repetitions inside the tree are found as well as those reaching back
+//! into the game. Only positions since the last irreversible move (or null
+//! move) are compared.
+
+use crate::syzygy::is_zeroing;
+use crate::types::{Bitboard, Board, Move, Piece};
+
+/// One position of the game or the search path
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    hash: u64,
+    /// Plies since the last capture or pawn move
+    rule50: u32,
+    /// Plies that can hold a repetition: since the last irreversible or null move
+    window: u32,
+}
+
+/// Positions from the start of the game to the current search node
+#[derive(Debug, Clone, Default)]
+pub struct PositionHistory {
+    entries: Vec<Entry>,
+}
+
+impl PositionHistory {
+    pub fn new() -> Self {
+        Self { entries: Vec::with_capacity(512) }
+    }
+
+    /// Start from the root of a search: `game` holds the hashes of the
+    /// positions played before the root, oldest first
+    pub fn reset(&mut self, game: &[u64], root_hash: u64, halfmove_clock: u32) {
+        self.entries.clear();
+        self.entries.extend(game.iter().map(|&hash| Entry { hash, rule50: 0, window: 0 }));
+        self.entries.push(Entry {
+            hash: root_hash,
+            rule50: halfmove_clock,
+            window: halfmove_clock,
+        });
+    }
+
+    /// Enter the position reached by playing `m` on `board`
+    pub fn push_move(&mut self, board: &Board, m: Move, hash: u64) {
+        let (rule50, window) = if is_zeroing(board, m) {
+            (0, 0)
+        } else {
+            let last = self.last();
+            (last.rule50 + 1, last.window + 1)
+        };
+        self.entries.push(Entry { hash, rule50, window });
+    }
+
+    /// Enter the position reached by a null move. Repetitions across it
+    /// don't count, since passing isn't a legal move.
+    pub fn push_null(&mut self, hash: u64) {
+        let rule50 = self.last().rule50 + 1;
+        self.entries.push(Entry { hash, rule50, window: 0 });
+    }
+
+    /// Leave the current position
+    pub fn pop(&mut self) {
+        self.entries.pop();
+    }
+
+    /// Plies since the last capture or pawn move
+    pub fn rule50(&self) -> u32 {
+        self.entries.last().map_or(0, |e| e.rule50)
+    }
+
+    /// Check if the current position is drawn by repetition.
+    ///
+    /// A single repetition counts once it lies within the search (less than
+    /// `ply` plies back, `ply` being the distance to the root); positions
+    /// that only repeat the game need to have occurred twice before.
+    pub fn is_repetition(&self, ply: usize) -> bool {
+        let mut count = 0;
+        for distance in self.earlier_occurrences() {
+            count += 1;
+            if distance < ply || count >= 2 {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Check if the current position occurred before (since the last
+    /// irreversible move)
+    pub fn has_repeated(&self) -> bool {
+        self.earlier_occurrences().next().is_some()
+    }
+
+    /// Distances back to earlier occurrences of the current position. The
+    /// same side must be to move, so only every other ply is compared.
+    fn earlier_occurrences(&self) -> impl Iterator<Item = usize> + '_ {
+        let len = self.entries.len();
+        let current = self.entries.last().copied();
+        let window = current.map_or(0, |e| e.window as usize).min(len.saturating_sub(1));
+
+        (4..=window)
+            .step_by(2)
+            .filter(move |&d| current.is_some_and(|c| self.entries[len - 1 - d].hash == c.hash))
+    }
+
+    fn last(&self) -> Entry {
+        self.entries.last().copied().unwrap_or(Entry { hash: 0, rule50: 0, window: 0 })
+    }
+}
+
+/// Check if neither side has enough material to ever checkmate: bare
+/// kings, a single minor piece, or only bishops all on one square colour
+pub fn is_insufficient_material(board: &Board) -> bool {
+    let heavy = board.piece_bb(Piece::Pawn) | board.piece_bb(Piece::Rook) | board.piece_bb(Piece::Queen);
+    if !heavy.is_empty() {
+        return false;
+    }
+
+    let knights = board.piece_bb(Piece::Knight);
+    let bishops = board.piece_bb(Piece::Bishop);
+    let minors = (knights | bishops).count();
+    if minors <= 1 {
+        return true;
+    }
+
+    knights.is_empty() && same_square_colour(bishops)
+}
+
+fn same_square_colour(bb: Bitboard) -> bool {
+    let mut colours = bb.into_iter().map(|sq| (sq.file().index() + sq.rank().index()) % 2);
+    match colours.next() {
+        Some(first) => colours.all(|c| c == first),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(hashes: &[u64], halfmove_clock: u32) -> PositionHistory {
+        let mut h = PositionHistory::new();
+        let (root, game) = hashes.split_last().unwrap();
+        h.reset(game, *root, halfmove_clock);
+        h
+    }
+
+    #[test]
+    fn test_game_repetitions_need_two_occurrences() {
+        // A B C D A: one earlier occurrence in the game
+        let h = history(&[1, 2, 3, 4, 1], 4);
+        assert!(h.has_repeated());
+        assert!(!h.is_repetition(0));
+
+        // A B C D A B C D A: threefold
+        let h = history(&[1, 2, 3, 4, 1, 2, 3, 4, 1], 8);
+        assert!(h.is_repetition(0));
+
+        // The repetition lies behind an irreversible move
+        let h = history(&[1, 2, 3, 4, 1, 2, 3, 4, 1], 3);
+        assert!(!h.has_repeated());
+        assert!(!h.is_repetition(0));
+    }
+
+    /// Push a reversible move (what `push_move` does for a quiet non-pawn move)
+    fn push_quiet(h: &mut PositionHistory, hash: u64) {
+        let last = h.last();
+        h.entries.push(Entry { hash, rule50: last.rule50 + 1, window: last.window + 1 });
+    }
+
+    #[test]
+    fn test_search_path_repetition() {
+        // Root 1, then 2 3 4 1: returning to the root alone isn't a draw
+        let mut h = history(&[1], 0);
+        for hash in [2, 3, 4, 1] {
+            push_quiet(&mut h, hash);
+        }
+        assert!(!h.is_repetition(4));
+        assert_eq!(h.rule50(), 4);
+
+        // ...but repeating a position first reached inside the tree is
+        push_quiet(&mut h, 2);
+        assert!(h.is_repetition(5));
+
+        // Null moves cut the repetition window
+        h.pop();
+        h.push_null(3);
+        push_quiet(&mut h, 4);
+        push_quiet(&mut h, 1);
+        push_quiet(&mut h, 2);
+        assert!(!h.has_repeated());
+        assert_eq!(h.rule50(), 8);
+    }
+}
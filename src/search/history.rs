@@ -1,9 +1,12 @@
 //! History heuristic for move ordering.
 //!
-//! Tracks which quiet moves cause beta cutoffs and uses
-//! accumulated scores to order moves better in future searches.
+//! Tracks which moves cause beta cutoffs and uses accumulated scores to
+//! order moves better in future searches:
+//! - `HistoryTable`: quiet moves by colour and from/to squares (butterfly)
+//! - `ContinuationHistory`: quiet moves by the move played 1 or 2 plies before
+//! - `CaptureHistory`: captures by moving piece, destination and victim
 
-use crate::types::{Move, Color};
+use crate::types::{Board, Color, Move, MoveFlag, Piece, Square};
 
 /// History table: [color][from_sq][to_sq] -> score
 #[derive(Clone)]
@@ -82,3 +85,251 @@ impl Default for HistoryTable {
         Self::new()
     }
 }
+
+/// A moved piece (with its colour) and its destination square: the key
+/// of continuation and capture history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceTo(u16);
+
+impl PieceTo {
+    /// Number of distinct keys: 12 coloured pieces x 64 squares
+    pub const COUNT: usize = 12 * 64;
+
+    #[inline]
+    pub fn new(color: Color, piece: Piece, to: Square) -> Self {
+        let piece = color.index() * 6 + piece.index();
+        Self((piece * 64 + to.index() as usize) as u16)
+    }
+
+    /// Key of a move on the board it is played from
+    #[inline]
+    pub fn of_move(board: &Board, mv: Move) -> Option<Self> {
+        let (piece, color) = board.piece_at(mv.from())?;
+        Some(Self::new(color, piece, mv.to()))
+    }
+
+    #[inline]
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Piece a move captures (a pawn for en passant)
+#[inline]
+pub fn captured_piece(board: &Board, mv: Move) -> Option<Piece> {
+    if mv.flag() == MoveFlag::EnPassant {
+        return Some(Piece::Pawn);
+    }
+    board.piece_at(mv.to()).map(|(p, _)| p)
+}
+
+/// Gravity update shared by the history tables: scores saturate at
+/// +-`HISTORY_MAX` instead of growing unbounded
+#[inline]
+fn apply_bonus(entry: &mut i16, bonus: i32) {
+    let old = *entry as i32;
+    let bonus = bonus.clamp(-HISTORY_MAX, HISTORY_MAX);
+    *entry = (old + bonus - old * bonus.abs() / HISTORY_MAX) as i16;
+}
+
+const HISTORY_MAX: i32 = 16384;
+
+/// Continuation history: [previous piece-to][piece-to] -> score.
+///
+/// Scores a quiet move by how well it followed the move played 1 or 2
+/// plies earlier (the same table serves both distances).
+#[derive(Clone)]
+pub struct ContinuationHistory {
+    table: Vec<i16>,
+}
+
+impl ContinuationHistory {
+    pub fn new() -> Self {
+        Self {
+            table: vec![0; PieceTo::COUNT * PieceTo::COUNT],
+        }
+    }
+
+    /// Get the score of `current` following `prev`
+    #[inline]
+    pub fn get(&self, prev: PieceTo, current: PieceTo) -> i32 {
+        self.table[prev.index() * PieceTo::COUNT + current.index()] as i32
+    }
+
+    /// Add `bonus` (negative for a penalty) to `current` following `prev`
+    #[inline]
+    pub fn update(&mut self, prev: PieceTo, current: PieceTo, bonus: i32) {
+        apply_bonus(&mut self.table[prev.index() * PieceTo::COUNT + current.index()], bonus);
+    }
+
+    /// Apply bonus to the quiet move that caused a cutoff and penalty to the
+    /// other quiet moves, following each of the previous moves `prev`
+    pub fn update_on_cutoff(
+        &mut self,
+        board: &Board,
+        prev: [Option<PieceTo>; 2],
+        best_move: Move,
+        depth: i32,
+        other_quiets: &[Move],
+    ) {
+        let bonus = depth * depth;
+        for prev in prev.into_iter().flatten() {
+            if let Some(best) = PieceTo::of_move(board, best_move) {
+                self.update(prev, best, bonus);
+            }
+            for &m in other_quiets {
+                if let Some(other) = PieceTo::of_move(board, m).filter(|_| m != best_move) {
+                    self.update(prev, other, -bonus);
+                }
+            }
+        }
+    }
+
+    /// Clear all scores (new game)
+    pub fn clear(&mut self) {
+        self.table.fill(0);
+    }
+
+    /// Halve all scores (new search)
+    pub fn age(&mut self) {
+        for entry in &mut self.table {
+            *entry /= 2;
+        }
+    }
+}
+
+impl Default for ContinuationHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Capture history: [piece-to][captured piece] -> score.
+///
+/// Refines MVV-LVA ordering with how often a capture actually caused a
+/// cutoff.
+#[derive(Clone)]
+pub struct CaptureHistory {
+    table: Vec<i16>,
+}
+
+impl CaptureHistory {
+    pub fn new() -> Self {
+        Self {
+            table: vec![0; PieceTo::COUNT * 6],
+        }
+    }
+
+    /// Get the score of a capture
+    #[inline]
+    pub fn get(&self, piece_to: PieceTo, captured: Piece) -> i32 {
+        self.table[piece_to.index() * 6 + captured.index()] as i32
+    }
+
+    /// Add `bonus` (negative for a penalty) to a capture
+    #[inline]
+    pub fn update(&mut self, piece_to: PieceTo, captured: Piece, bonus: i32) {
+        apply_bonus(&mut self.table[piece_to.index() * 6 + captured.index()], bonus);
+    }
+
+    /// Score of a capture move on `board` (0 for other moves)
+    #[inline]
+    pub fn get_move(&self, board: &Board, mv: Move) -> i32 {
+        match (PieceTo::of_move(board, mv), captured_piece(board, mv)) {
+            (Some(piece_to), Some(captured)) => self.get(piece_to, captured),
+            _ => 0,
+        }
+    }
+
+    /// After a cutoff by `best_move`: bonus if it was a capture, penalty for
+    /// the captures searched before it
+    pub fn update_on_cutoff(&mut self, board: &Board, best_move: Move, depth: i32, other_captures: &[Move]) {
+        let bonus = depth * depth;
+        let mut update = |m: Move, bonus: i32| {
+            if let (Some(piece_to), Some(captured)) = (PieceTo::of_move(board, m), captured_piece(board, m)) {
+                self.update(piece_to, captured, bonus);
+            }
+        };
+
+        if best_move.is_capture() {
+            update(best_move, bonus);
+        }
+        for &m in other_captures {
+            if m != best_move {
+                update(m, -bonus);
+            }
+        }
+    }
+
+    /// Clear all scores (new game)
+    pub fn clear(&mut self) {
+        self.table.fill(0);
+    }
+
+    /// Halve all scores (new search)
+    pub fn age(&mut self) {
+        for entry in &mut self.table {
+            *entry /= 2;
+        }
+    }
+}
+
+impl Default for CaptureHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The history tables of one search thread, seen from one node
+pub struct HistoryContext<'a> {
+    pub history: &'a HistoryTable,
+    pub cont_history: &'a ContinuationHistory,
+    pub capture_history: &'a CaptureHistory,
+    /// Moves played 1 and 2 plies before the node
+    pub prev: [Option<PieceTo>; 2],
+    /// Side to move at the node
+    pub color: Color,
+}
+
+impl HistoryContext<'_> {
+    /// Butterfly plus continuation history of a quiet move
+    #[inline]
+    pub fn quiet(&self, board: &Board, mv: Move) -> i32 {
+        let mut score = self.history.get(self.color, mv);
+        if let Some(current) = PieceTo::of_move(board, mv) {
+            for prev in self.prev.into_iter().flatten() {
+                score += self.cont_history.get(prev, current);
+            }
+        }
+        score
+    }
+
+    /// Capture history of a capture
+    #[inline]
+    pub fn capture(&self, board: &Board, mv: Move) -> i32 {
+        self.capture_history.get_move(board, mv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_continuation_history_saturates() {
+        let mut cont = ContinuationHistory::new();
+        let prev = PieceTo::new(Color::White, Piece::Knight, Square::F3);
+        let reply = PieceTo::new(Color::Black, Piece::Knight, Square::F6);
+        let other = PieceTo::new(Color::Black, Piece::Pawn, Square::F6);
+
+        for _ in 0..1000 {
+            cont.update(prev, reply, 400);
+        }
+        assert!(cont.get(prev, reply) > 0 && cont.get(prev, reply) <= HISTORY_MAX);
+        assert_eq!(cont.get(prev, other), 0);
+        assert_eq!(cont.get(reply, prev), 0);
+
+        cont.age();
+        assert!(cont.get(prev, reply) <= HISTORY_MAX / 2);
+    }
+}
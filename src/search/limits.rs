@@ -3,11 +3,12 @@
 //! Handles:
 //! - Fixed depth search
 //! - Fixed time search
+//! - Fixed node and mate-in-N search (enforced by the searcher)
 //! - Time control with increment
 //! - Infinite search (until stop)
 //! - Soft/hard time limits for optimal iteration control
 
-use crate::types::{Depth, Color};
+use crate::types::{Depth, Color, Move};
 use crate::uci::SearchParams;
 use std::time::Instant;
 
@@ -18,8 +19,10 @@ pub struct SearchLimits {
     pub depth: Option<Depth>,
     /// Maximum time in milliseconds
     pub movetime: Option<u64>,
-    /// Maximum nodes to search
+    /// Maximum nodes to search (summed over all threads)
     pub nodes: Option<u64>,
+    /// Stop once a mate in this many moves (or fewer) is found
+    pub mate: Option<u32>,
     /// White time remaining (ms)
     pub wtime: Option<u64>,
     /// Black time remaining (ms)
@@ -32,6 +35,8 @@ pub struct SearchLimits {
     pub movestogo: Option<u32>,
     /// Infinite search
     pub infinite: bool,
+    /// Only search these root moves (empty = all moves)
+    pub searchmoves: Vec<Move>,
     /// Move overhead (safety buffer for network/GUI delay)
     pub move_overhead: u64,
 }
@@ -55,17 +60,27 @@ impl SearchLimits {
         }
     }
 
+    pub fn movetime(ms: u64) -> Self {
+        Self {
+            movetime: Some(ms),
+            move_overhead: Self::DEFAULT_MOVE_OVERHEAD,
+            ..Default::default()
+        }
+    }
+
     pub fn from_params(params: &SearchParams) -> Self {
         Self {
             depth: params.depth,
             movetime: params.movetime,
             nodes: params.nodes,
+            mate: params.mate,
             wtime: params.wtime,
             btime: params.btime,
             winc: params.winc,
             binc: params.binc,
             movestogo: params.movestogo,
             infinite: params.infinite,
+            searchmoves: params.searchmoves.clone(),
             move_overhead: Self::DEFAULT_MOVE_OVERHEAD,
         }
     }
@@ -75,6 +90,15 @@ impl SearchLimits {
         self.move_overhead = overhead;
         self
     }
+
+    /// Charge time already spent on this move (e.g. checking book moves)
+    /// to `movetime` and the clocks
+    pub fn with_time_used(mut self, ms: u64) -> Self {
+        self.movetime = self.movetime.map(|t| t.saturating_sub(ms));
+        self.wtime = self.wtime.map(|t| t.saturating_sub(ms));
+        self.btime = self.btime.map(|t| t.saturating_sub(ms));
+        self
+    }
 }
 
 /// Time manager for search with soft and hard limits
@@ -305,6 +329,21 @@ mod tests {
         assert_eq!(tm.hard_limit_ms(), 902);
     }
     
+    #[test]
+    fn test_time_used() {
+        let limits = SearchLimits {
+            movetime: Some(1000),
+            wtime: Some(60000),
+            btime: Some(100),
+            ..Default::default()
+        }
+        .with_time_used(250);
+        assert_eq!(limits.movetime, Some(750));
+        assert_eq!(limits.wtime, Some(59750));
+        assert_eq!(limits.btime, Some(0));
+        assert_eq!(limits.winc, None);
+    }
+
     #[test]
     fn test_time_control() {
         let limits = SearchLimits {
@@ -340,4 +379,20 @@ mod tests {
         assert!(tm.can_start_iteration());
         assert!(!tm.should_stop());
     }
+    
+    #[test]
+    fn test_node_and_mate_limits_from_params() {
+        let params = SearchParams {
+            nodes: Some(50_000),
+            mate: Some(3),
+            ..Default::default()
+        };
+        let limits = SearchLimits::from_params(&params);
+        
+        assert_eq!(limits.nodes, Some(50_000));
+        assert_eq!(limits.mate, Some(3));
+        // No clock given: only the searcher's node/mate checks end the search
+        let tm = TimeManager::from_limits(&limits, Color::White);
+        assert!(tm.is_infinite());
+    }
 }
@@ -0,0 +1,200 @@
+//! Search event reporting.
+//!
+//! The searcher doesn't print anything itself: it reports progress to a
+//! `SearchListener`. The UCI handler installs one that writes `info` and
+//! `bestmove` lines; library users can keep the default `SilentListener`
+//! or record events with a `CollectingListener`.
+//!
+//! Only the main search thread reports events.
+
+use super::tt::BoundType;
+use super::SearchStats;
+use crate::types::{Board, Move, Score};
+use std::sync::Mutex;
+
+/// One principal variation at the end of an iteration, or an aspiration
+/// fail-high/low while the window is re-searched
+#[derive(Debug, Clone)]
+pub struct PvInfo {
+    pub depth: i32,
+    pub seldepth: i32,
+    /// 1-based MultiPV index
+    pub multipv: usize,
+    /// Score from the root side's perspective
+    pub score: Score,
+    /// `Some` when `score` is only a bound (aspiration fail-high/low)
+    pub bound: Option<BoundType>,
+    /// Nodes searched by all threads
+    pub nodes: u64,
+    pub nps: u64,
+    pub time_ms: u64,
+    /// TT occupancy in permill
+    pub hashfull: u32,
+    pub tb_hits: u64,
+    pub pv: Vec<Move>,
+}
+
+/// Root move currently being searched
+#[derive(Debug, Clone, Copy)]
+pub struct CurrMoveInfo {
+    pub depth: i32,
+    pub mv: Move,
+    /// 1-based index of the move in search order
+    pub number: usize,
+}
+
+/// Periodic progress while no iteration completes
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressInfo {
+    pub nodes: u64,
+    pub nps: u64,
+    pub time_ms: u64,
+    pub hashfull: u32,
+    pub tb_hits: u64,
+}
+
+/// Receives search events. Every method has an empty default, so
+/// implementations only override what they need.
+///
+/// `board` is always the root position, which moves are relative to.
+pub trait SearchListener: Send + Sync {
+    /// A principal variation was updated
+    fn on_pv(&self, _board: &Board, _info: &PvInfo) {}
+
+    /// An iteration completed; its lines were reported through `on_pv`
+    fn on_iteration(&self, _board: &Board, _stats: &SearchStats) {}
+
+    /// A new root move is being searched
+    fn on_currmove(&self, _board: &Board, _info: &CurrMoveInfo) {}
+
+    /// Periodic node count update
+    fn on_progress(&self, _info: &ProgressInfo) {}
+
+    /// The search finished with this move (and the expected reply to ponder on)
+    fn on_bestmove(&self, _board: &Board, _best: Option<Move>, _ponder: Option<Move>) {}
+}
+
+/// Listener that ignores every event
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SilentListener;
+
+impl SearchListener for SilentListener {}
+
+/// A recorded search event
+#[derive(Debug, Clone)]
+pub enum SearchEvent {
+    Pv(PvInfo),
+    Iteration(SearchStats),
+    CurrMove(CurrMoveInfo),
+    Progress(ProgressInfo),
+    BestMove { best: Option<Move>, ponder: Option<Move> },
+}
+
+/// Listener that records every event, for tests and library users
+#[derive(Debug, Default)]
+pub struct CollectingListener {
+    events: Mutex<Vec<SearchEvent>>,
+}
+
+impl CollectingListener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove and return the events recorded so far
+    pub fn take(&self) -> Vec<SearchEvent> {
+        std::mem::take(&mut *self.lock())
+    }
+
+    /// Copy of the events recorded so far
+    pub fn events(&self) -> Vec<SearchEvent> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<SearchEvent>> {
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, event: SearchEvent) {
+        self.lock().push(event);
+    }
+}
+
+impl SearchListener for CollectingListener {
+    fn on_pv(&self, _board: &Board, info: &PvInfo) {
+        self.push(SearchEvent::Pv(info.clone()));
+    }
+
+    fn on_iteration(&self, _board: &Board, stats: &SearchStats) {
+        self.push(SearchEvent::Iteration(stats.clone()));
+    }
+
+    fn on_currmove(&self, _board: &Board, info: &CurrMoveInfo) {
+        self.push(SearchEvent::CurrMove(*info));
+    }
+
+    fn on_progress(&self, info: &ProgressInfo) {
+        self.push(SearchEvent::Progress(*info));
+    }
+
+    fn on_bestmove(&self, _board: &Board, best: Option<Move>, ponder: Option<Move>) {
+        self.push(SearchEvent::BestMove { best, ponder });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collecting_listener() {
+        let listener = CollectingListener::new();
+        let board = Board::default();
+        listener.on_progress(&ProgressInfo { nodes: 10, nps: 100, time_ms: 100, hashfull: 0, tb_hits: 0 });
+        listener.on_bestmove(&board, Some(Move::NULL), None);
+
+        assert_eq!(listener.events().len(), 2);
+        let events = listener.take();
+        assert!(matches!(events[0], SearchEvent::Progress(ProgressInfo { nodes: 10, .. })));
+        assert!(matches!(events[1], SearchEvent::BestMove { best: Some(_), ponder: None }));
+        assert!(listener.take().is_empty());
+    }
+
+    #[test]
+    fn test_searcher_reports_through_listener() {
+        use crate::search::{SearchLimits, Searcher};
+        use std::sync::Arc;
+
+        let listener = Arc::new(CollectingListener::new());
+        let mut searcher = Searcher::with_hash_size(1);
+        searcher.set_listener(listener.clone());
+        searcher.set_position(Board::default());
+        let result = searcher.search(SearchLimits::depth(3));
+
+        // Each depth reports its line and then the iteration, in order,
+        // and the search ends with exactly one bestmove: the result's
+        let mut expected_depth = 1;
+        let mut pv_seen = false;
+        let mut bestmoves = Vec::new();
+        for event in listener.take() {
+            match event {
+                SearchEvent::Pv(info) if info.bound.is_none() => {
+                    assert!(bestmoves.is_empty());
+                    assert_eq!(info.depth, expected_depth);
+                    pv_seen = true;
+                }
+                SearchEvent::Iteration(stats) => {
+                    assert!(pv_seen);
+                    assert_eq!(stats.depth.raw(), expected_depth);
+                    expected_depth += 1;
+                    pv_seen = false;
+                }
+                SearchEvent::BestMove { best, .. } => bestmoves.push(best),
+                _ => {}
+            }
+        }
+        assert_eq!(expected_depth, 4);
+        assert_eq!(bestmoves, vec![result.best_move]);
+        assert!(result.best_move.is_some());
+    }
+}
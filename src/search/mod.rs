@@ -6,8 +6,10 @@
 //! - `Searcher`: Main search controller with state management
 //! - `negamax`: Alpha-beta search with negamax framework
 //! - `ordering`: Move ordering heuristics (MVV-LVA, killer moves, history)
+//! - `movepicker`: Staged move picker handing moves to the search lazily
 //! - `limits`: Search limits and time management
 //! - `tt`: Transposition table for caching search results
+//! - `listener`: Search output (`info`/`bestmove`) as events for a `SearchListener`
 //!
 //! # Multi-threading
 //! Implements Lazy SMP with lock-free TT sharing between threads
@@ -15,31 +17,51 @@
 mod negamax;
 mod qsearch;
 mod ordering;
+mod movepicker;
 mod limits;
 pub mod tt;
 mod killers;
 mod history;
 mod see;
 mod countermove;
+mod stack;
 pub mod node_types;
 mod correction;
+mod draw;
+pub mod listener;
+mod skill;
 
 pub use node_types::{NodeType, Root, OnPV, OffPV};
 pub use correction::CorrectionHistoryTable;
+pub use skill::Skill;
+pub use draw::{PositionHistory, is_insufficient_material};
+pub use listener::{SearchListener, SilentListener, CollectingListener, SearchEvent, PvInfo, CurrMoveInfo, ProgressInfo};
 
 pub use limits::{SearchLimits, TimeManager};
 pub use negamax::SearchResult;
 pub use tt::TranspositionTable;
 pub use killers::KillerTable;
-pub use history::HistoryTable;
+pub use history::{HistoryTable, ContinuationHistory, CaptureHistory, HistoryContext, PieceTo};
+pub use stack::SearchStack;
 pub use countermove::CounterMoveTable;
 pub use see::{see, see_ge, is_good_capture};
 
-use crate::types::{Board, Move, Score, Depth, Ply, NodeCount};
+use crate::types::{Board, Color, Move, Score, Depth, Ply, Piece, NodeCount, Prng};
+use crate::types::castling::CastlingRooks;
 use crate::eval::{nnue, SearchEvaluator};
+use crate::syzygy::{self, Tablebases, Wdl};
+use tt::BoundType;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
+
+/// Don't report aspiration bounds or the current root move during the
+/// first second of a search, when iterations complete quickly anyway (ms)
+const INFO_DELAY_MS: u64 = 1000;
+
+/// Interval between `info nodes` progress lines while no iteration completes (ms)
+const HEARTBEAT_MS: u64 = 1000;
 
 /// Search statistics collected during search
 #[derive(Debug, Clone, Default)]
@@ -51,6 +73,10 @@ pub struct SearchStats {
     pub hashfull: u32,
     pub qnodes: NodeCount,
     pub eval_calls: u64,
+    /// Time at which the current best move was first chosen (ms)
+    pub best_move_time_ms: u64,
+    /// Depth at which the current best move was first chosen
+    pub best_move_depth: Depth,
     // Profiling stats (ns)
     pub time_gen: u64,
     pub time_eval: u64,
@@ -67,31 +93,49 @@ impl SearchStats {
         }
     }
 
-    pub fn print_profiling(&self) {
+    /// One-line breakdown of where the search time went (main thread only)
+    pub fn profile_summary(&self) -> Option<String> {
         let total_ns = self.time_ms * 1_000_000;
-        if total_ns > 0 {
-            let gen_pct = self.time_gen * 100 / total_ns;
-            let eval_pct = self.time_eval * 100 / total_ns;
-            let order_pct = self.time_order * 100 / total_ns;
-            let other = total_ns.saturating_sub(self.time_gen + self.time_eval + self.time_order);
-            let other_pct = other * 100 / total_ns;
-            
-            println!("profiling: gen {}% eval {}% order {}% other {}%", 
-                gen_pct, eval_pct, order_pct, other_pct);
-            
-             println!("stats: qnodes {} evals {}", self.qnodes, self.eval_calls);
+        if total_ns == 0 {
+            return None;
         }
+
+        let gen_pct = self.time_gen * 100 / total_ns;
+        let eval_pct = self.time_eval * 100 / total_ns;
+        let order_pct = self.time_order * 100 / total_ns;
+        let other = total_ns.saturating_sub(self.time_gen + self.time_eval + self.time_order);
+        let other_pct = other * 100 / total_ns;
+
+        Some(format!(
+            "profiling: gen {}% eval {}% order {}% other {}% qnodes {} evals {}",
+            gen_pct, eval_pct, order_pct, other_pct, self.qnodes, self.eval_calls
+        ))
     }
 }
 
+/// One principal variation of a MultiPV search
+#[derive(Debug, Clone)]
+pub struct PvLine {
+    /// Score of the line from the root side's perspective
+    pub score: Score,
+    /// Moves of the line, starting with the root move
+    pub pv: Vec<Move>,
+    /// Iteration that completed the line
+    pub depth: i32,
+}
+
 /// Shared state between search threads
 pub struct SharedState {
     /// Lock-free transposition table
     pub tt: TranspositionTable,
     /// Global stop flag
     pub stop: AtomicBool,
+    /// Set while pondering: time limits are ignored until `ponderhit` clears it
+    pub ponder: AtomicBool,
     /// Total nodes searched (sum across all threads)
     pub total_nodes: AtomicU64,
+    /// Successful tablebase probes (sum across all threads)
+    pub tb_hits: AtomicU64,
 }
 
 impl SharedState {
@@ -99,7 +143,9 @@ impl SharedState {
         Self {
             tt: TranspositionTable::new(hash_size_mb),
             stop: AtomicBool::new(false),
+            ponder: AtomicBool::new(false),
             total_nodes: AtomicU64::new(0),
+            tb_hits: AtomicU64::new(0),
         }
     }
 }
@@ -122,20 +168,75 @@ pub struct Searcher {
     pub history: HistoryTable,
     /// Counter-move table (per-thread)
     pub countermoves: CounterMoveTable,
+    /// Continuation history, 1 and 2 plies back (per-thread)
+    pub cont_history: ContinuationHistory,
+    /// Capture history (per-thread)
+    pub capture_history: CaptureHistory,
+    /// Pieces moved along the current search path (per-thread)
+    pub stack: SearchStack,
     /// Correction history table (per-thread)
     pub correction: CorrectionHistoryTable,
     /// Time manager for search limits
     time_manager: TimeManager,
+    /// Node budget for the whole search, across all threads (`go nodes`)
+    node_limit: Option<NodeCount>,
+    /// Stop once a mate in this many moves is found (`go mate`)
+    mate_limit: Option<u32>,
     /// Search statistics
     stats: SearchStats,
+    /// Local nodes already added to `SharedState::total_nodes`
+    flushed_nodes: NodeCount,
     /// Best move found so far
     best_move: Option<Move>,
     /// Principal variation
     pv: Vec<Move>,
+    /// Number of principal variations to report (MultiPV)
+    multi_pv: usize,
+    /// Lines the running search reports; a weakened search searches more
+    /// lines than the user asked for but only reports those
+    report_lines: usize,
+    /// Best lines from the last completed iteration, best first
+    pv_lines: Vec<PvLine>,
+    /// Root moves the search is restricted to (`go searchmoves`, empty = all)
+    pub root_moves: Vec<Move>,
+    /// Root moves skipped by the current root search (MultiPV)
+    pub excluded_root_moves: Vec<Move>,
     /// NNUE Model (thread-safe reference)
     pub nnue: Option<nnue::Model>,
-    /// Position history for repetition detection (stores Zobrist hashes)
-    pub position_history: Vec<u64>,
+    /// Hashes of the game positions before the root, oldest first
+    game_history: Vec<u64>,
+    /// Game positions plus the current search path, for draw detection
+    pub positions: PositionHistory,
+    /// Fifty-move counter of the root position, in plies
+    halfmove_clock: u32,
+    /// Rooks holding the castling rights of the game (Chess960)
+    castling_rooks: CastlingRooks,
+    /// Syzygy tablebases (shared by all threads)
+    pub tablebases: Option<Arc<Tablebases>>,
+    /// Minimum depth to probe positions with the most pieces the tables cover
+    tb_probe_depth: i32,
+    /// Score cursed wins and blessed losses as draws (fifty-move rule)
+    tb_use_rule50: bool,
+    /// Most pieces probed inside the search (0 = no probes this search)
+    tb_cardinality: usize,
+    /// Tablebase score of the root, when its moves were ranked by the tables
+    tb_root_score: Option<Score>,
+    /// `Contempt` option: how much the root side dislikes a draw (centipawns)
+    contempt: i32,
+    /// Keep contempt for `go infinite` searches (off = analyse draws as 0)
+    analysis_contempt: bool,
+    /// Contempt applied by the current search
+    search_contempt: i32,
+    /// Strength limitation (full strength by default)
+    skill: Skill,
+    /// Random source for weakened move choice
+    skill_rng: Prng,
+    /// Whether the last search was ended by a stop request
+    stop_requested: bool,
+    /// Receives search output (shared with helper threads, which stay silent)
+    listener: Arc<dyn SearchListener>,
+    /// Search time at which the next progress line is due (ms)
+    next_heartbeat_ms: u64,
     /// Move stability counter (how many iterations best move unchanged)
     stable_move_count: u32,
     /// Last iteration's best move for stability tracking
@@ -147,6 +248,11 @@ pub struct Searcher {
 }
 
 impl Searcher {
+    /// Largest transposition table size accepted, in MB
+    pub const MAX_HASH_MB: usize = 65536;
+    /// Default `Contempt`, in centipawns
+    pub const DEFAULT_CONTEMPT: i32 = 10;
+
     pub fn new() -> Self {
         Self {
             board: Board::default(),
@@ -154,13 +260,40 @@ impl Searcher {
             killers: KillerTable::new(),
             history: HistoryTable::new(),
             countermoves: CounterMoveTable::new(),
+            cont_history: ContinuationHistory::new(),
+            capture_history: CaptureHistory::new(),
+            stack: SearchStack::new(),
             correction: CorrectionHistoryTable::new(),
             time_manager: TimeManager::new(),
+            node_limit: None,
+            mate_limit: None,
             stats: SearchStats::default(),
+            flushed_nodes: 0,
             best_move: None,
             pv: Vec::new(),
+            multi_pv: 1,
+            report_lines: 1,
+            pv_lines: Vec::new(),
+            root_moves: Vec::new(),
+            excluded_root_moves: Vec::new(),
             nnue: None,
-            position_history: Vec::with_capacity(512),
+            game_history: Vec::new(),
+            positions: PositionHistory::new(),
+            halfmove_clock: 0,
+            castling_rooks: CastlingRooks::default(),
+            tablebases: None,
+            tb_probe_depth: 1,
+            tb_use_rule50: true,
+            tb_cardinality: 0,
+            tb_root_score: None,
+            contempt: Self::DEFAULT_CONTEMPT,
+            analysis_contempt: false,
+            search_contempt: Self::DEFAULT_CONTEMPT,
+            skill: Skill::default(),
+            skill_rng: Prng::from_time(),
+            stop_requested: false,
+            listener: Arc::new(SilentListener),
+            next_heartbeat_ms: HEARTBEAT_MS,
             stable_move_count: 0,
             last_best_move: None,
             num_threads: 1,
@@ -174,6 +307,41 @@ impl Searcher {
         s.shared = Arc::new(SharedState::new(size_mb));
         s
     }
+
+    /// Rebuild the transposition table with a new size in MB.
+    ///
+    /// This replaces `shared`, so callers holding a clone of the old
+    /// `Arc<SharedState>` must refresh it.
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.shared = Arc::new(SharedState::new(size_mb.clamp(1, Self::MAX_HASH_MB)));
+    }
+
+    /// Get the transposition table size in MB
+    pub fn hash_size(&self) -> usize {
+        self.shared.tt.size_mb()
+    }
+
+    /// Clear the transposition table without reallocating it
+    pub fn clear_hash(&self) {
+        self.shared.tt.clear();
+    }
+
+    /// Forget everything learned in the previous game.
+    ///
+    /// Clears the TT and the per-thread heuristic tables but keeps the
+    /// table allocation, thread count and NNUE model.
+    pub fn new_game(&mut self) {
+        self.clear_hash();
+        self.killers.clear();
+        self.history.clear();
+        self.countermoves.clear();
+        self.cont_history.clear();
+        self.capture_history.clear();
+        self.correction.clear();
+        self.game_history.clear();
+        self.halfmove_clock = 0;
+        self.board = Board::default();
+    }
     
     /// Set number of search threads
     pub fn set_threads(&mut self, threads: usize) {
@@ -185,31 +353,115 @@ impl Searcher {
         self.num_threads
     }
 
+    /// Set the number of principal variations to search and report
+    pub fn set_multi_pv(&mut self, lines: usize) {
+        self.multi_pv = lines.clamp(1, 256);
+    }
+
+    /// Get the number of principal variations searched
+    pub fn multi_pv(&self) -> usize {
+        self.multi_pv
+    }
+
     /// Set NNUE model
     pub fn set_nnue(&mut self, model: Option<nnue::Model>) {
         self.nnue = model;
     }
 
-    /// Set the position to search with history for repetition detection
+    /// Set the Syzygy tablebases to probe (`None` disables probing)
+    pub fn set_tablebases(&mut self, tablebases: Option<Arc<Tablebases>>) {
+        self.tablebases = tablebases;
+    }
+
+    /// Set the minimum depth for probing positions with the most pieces
+    pub fn set_tb_probe_depth(&mut self, depth: i32) {
+        self.tb_probe_depth = depth.max(1);
+    }
+
+    /// Choose whether tablebase wins spoiled by the fifty-move rule count as draws
+    pub fn set_tb_use_rule50(&mut self, use_rule50: bool) {
+        self.tb_use_rule50 = use_rule50;
+    }
+
+    /// Set how much the root side dislikes draws, in centipawns (negative
+    /// values make it seek them)
+    pub fn set_contempt(&mut self, contempt: i32) {
+        self.contempt = contempt;
+    }
+
+    /// Choose whether `go infinite` (analysis) searches apply contempt
+    pub fn set_analysis_contempt(&mut self, enabled: bool) {
+        self.analysis_contempt = enabled;
+    }
+
+    /// Score of a draw for the side to move at `ply`.
+    ///
+    /// Contempt is taken from the root side's perspective, so every thread
+    /// and iteration scores the same draw the same way: the root side sees
+    /// `-contempt` and its opponent `+contempt`.
+    #[inline]
+    pub fn draw_score(&self, ply: Ply) -> Score {
+        if ply.raw() % 2 == 0 {
+            Score::cp(-self.search_contempt)
+        } else {
+            Score::cp(self.search_contempt)
+        }
+    }
+
+    /// Limit playing strength (`Skill::default()` plays at full strength)
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+    }
+
+    /// Send search output to `listener` (the default discards it)
+    pub fn set_listener(&mut self, listener: Arc<dyn SearchListener>) {
+        self.listener = listener;
+    }
+
+    /// Set the fifty-move counter of the position to search
+    pub fn set_halfmove_clock(&mut self, plies: u32) {
+        self.halfmove_clock = plies;
+    }
+
+    /// Set the rooks holding the castling rights, as read from the FEN the
+    /// game started from
+    pub fn set_castling_rooks(&mut self, castling_rooks: CastlingRooks) {
+        self.castling_rooks = castling_rooks;
+    }
+
+    /// Set the position to search, without game history
     pub fn set_position(&mut self, board: Board) {
-        self.position_history.clear();
-        self.position_history.push(board.hash());
+        self.game_history.clear();
         self.board = board;
     }
     
-    /// Set position with move history for repetition detection
+    /// Set the position to search along with the hashes of the game
+    /// positions played before it (oldest first), for repetition detection
     pub fn set_position_with_history(&mut self, board: Board, history: Vec<u64>) {
-        self.position_history = history;
-        self.position_history.push(board.hash());
+        self.game_history = history;
         self.board = board;
     }
     
-    /// Check if position has repeated (for draw detection)
-    /// Check if position has repeated (for draw detection)
-    pub fn is_repetition(&self, hash: u64) -> bool {
-        // Iterate backwards to find recent repetitions (more likely)
-        // using any() to stop at first match
-        self.position_history.iter().rev().any(|&h| h == hash)
+    /// History tables for ordering and pruning the moves of the node at `ply`
+    pub fn history_context(&self, color: Color, ply: Ply) -> HistoryContext<'_> {
+        HistoryContext {
+            history: &self.history,
+            cont_history: &self.cont_history,
+            capture_history: &self.capture_history,
+            prev: self.stack.continuation(ply),
+            color,
+        }
+    }
+
+    /// Check if the current search node is a draw: by repetition, the
+    /// fifty-move rule or insufficient material
+    pub fn is_draw(&self, board: &Board, ply: Ply) -> bool {
+        if self.positions.is_repetition(ply.raw() as usize) || is_insufficient_material(board) {
+            return true;
+        }
+
+        // After 100 reversible plies it's a draw, unless the last one mated
+        self.positions.rule50() >= 100 && !(board.in_check() && board.generate_moves().is_empty())
     }
 
     /// Get current statistics
@@ -227,11 +479,50 @@ impl Searcher {
         &self.pv
     }
 
+    /// Get all lines of the last MultiPV search, best first
+    pub fn pv_lines(&self) -> &[PvLine] {
+        &self.pv_lines
+    }
+
+    /// Whether the last search was ended by a stop request (`stop`) rather
+    /// than by its own limits. The stop flag itself is cleared on return.
+    pub fn stop_requested(&self) -> bool {
+        self.stop_requested
+    }
+
     /// Signal the search to stop
     pub fn stop(&mut self) {
         self.shared.stop.store(true, Ordering::Relaxed);
     }
 
+    /// Switch a ponder search over to the real time limits.
+    ///
+    /// The time manager keeps counting from the start of the ponder search,
+    /// so the time already spent pondering counts against the budget.
+    pub fn ponderhit(&self) {
+        self.shared.ponder.store(false, Ordering::Relaxed);
+    }
+
+    /// Check if the search is pondering (searching on the opponent's time)
+    #[inline]
+    pub fn is_pondering(&self) -> bool {
+        self.shared.ponder.load(Ordering::Relaxed)
+    }
+
+    /// Pick the move to ponder on after playing `result.best_move`.
+    ///
+    /// Uses the second PV move, falling back to the TT move of the position
+    /// after the best move when the PV was cut short.
+    pub fn ponder_move(&self, result: &SearchResult) -> Option<Move> {
+        if let Some(&m) = result.pv.get(1) {
+            return Some(m);
+        }
+
+        let next = self.board.make_move_new(result.best_move?);
+        let tt_move = self.shared.tt.probe(next.hash())?.best_move()?;
+        next.generate_moves().iter().find(|&m| m == tt_move)
+    }
+
     /// Check if search should stop (hard time limit, nodes limit, etc.)
     pub fn should_stop(&self) -> bool {
         // Check global stop flag
@@ -239,9 +530,15 @@ impl Searcher {
             return true;
         }
         
+        // Node budget is shared by all threads
+        if self.node_limit_reached() {
+            return true;
+        }
+        
         // Check time periodically (every 512 nodes for stricter timing)
         // More frequent checks help prevent time losses in movetime mode
-        if self.stats.nodes & 511 == 0 {
+        // No time limits apply while pondering
+        if self.stats.nodes & 511 == 0 && !self.is_pondering() {
             if self.time_manager.hard_limit_exceeded() {
                 return true;
             }
@@ -250,12 +547,36 @@ impl Searcher {
         false
     }
     
+    /// Check if the search has used up its node budget (`go nodes`)
+    #[inline]
+    fn node_limit_reached(&self) -> bool {
+        self.node_limit
+            .is_some_and(|limit| self.shared.total_nodes.load(Ordering::Relaxed) >= limit)
+    }
+
+    /// Check if a mate within the `go mate` limit has been found
+    fn mate_limit_reached(&self, score: Score) -> bool {
+        match (self.mate_limit, score.mate_distance()) {
+            (Some(moves), Some(plies)) => score.is_mate() && (plies + 1) / 2 <= moves as i32,
+            _ => false,
+        }
+    }
+
     /// Check if we can start a new iteration (soft time limit)
     fn can_start_new_iteration(&self) -> bool {
         if self.shared.stop.load(Ordering::Relaxed) {
             return false;
         }
         
+        if self.node_limit_reached() {
+            return false;
+        }
+        
+        // The clock isn't ours yet: keep deepening until ponderhit
+        if self.is_pondering() {
+            return true;
+        }
+        
         // Check soft limit
         if !self.time_manager.can_start_iteration() {
             return false;
@@ -276,6 +597,110 @@ impl Searcher {
         true
     }
     
+    /// Restrict the root moves to the best ones according to the tablebases.
+    ///
+    /// Ranking by DTZ makes the search convert won endgames within the
+    /// fifty-move rule, so the tables needn't be probed inside the search.
+    /// With WDL tables only, interior probes stay on while winning.
+    fn rank_root_tablebases(&mut self) {
+        self.tb_root_score = None;
+        self.tb_cardinality = 0;
+
+        let Some(tb) = self.tablebases.clone() else { return };
+        self.tb_cardinality = tb.max_pieces();
+
+        let board = self.board;
+        if board.occupied().count() as usize > self.tb_cardinality
+            || syzygy::has_castling_rights(&board)
+        {
+            return;
+        }
+
+        let moves: Vec<Move> = if self.root_moves.is_empty() {
+            board.generate_moves().iter().collect()
+        } else {
+            self.root_moves.clone()
+        };
+
+        // Repeating the root again risks a draw, so wins aren't certain
+        let repeated = self.positions.has_repeated();
+
+        // Moves that draw at once by repetition or the fifty-move rule
+        let draws: Vec<bool> = moves.iter().map(|&m| {
+            let next = board.make_move_new(m);
+            self.positions.push_move(&board, m, next.hash());
+            let draw = self.is_draw(&next, Ply::ZERO.next());
+            self.positions.pop();
+            draw
+        }).collect();
+
+        let (ranked, dtz_available) = match tb.rank_root_moves(
+            &board, &moves, &draws, self.halfmove_clock, repeated, self.tb_use_rule50,
+        ) {
+            Some(ranked) => (ranked, true),
+            None => match tb.rank_root_moves_wdl(&board, &moves, self.tb_use_rule50) {
+                Some(ranked) => (ranked, false),
+                None => return,
+            },
+        };
+
+        let Some(best) = ranked.iter().max_by_key(|m| m.rank).copied() else { return };
+        self.shared.tb_hits.fetch_add(ranked.len() as u64, Ordering::Relaxed);
+        self.root_moves = ranked.iter()
+            .filter(|m| m.rank == best.rank)
+            .map(|m| m.mv)
+            .collect();
+        self.tb_root_score = Some(best.score);
+
+        if dtz_available || best.score <= Score::draw() {
+            self.tb_cardinality = 0;
+        }
+    }
+
+    /// Probe the WDL tables at an interior node.
+    ///
+    /// Only positions right after a capture or pawn move are probed, where
+    /// the fifty-move counter is known to be zero. Returns the score and the
+    /// bound it represents.
+    pub fn probe_tablebases(
+        &self,
+        board: &Board,
+        depth: Depth,
+        ply: Ply,
+        prev_move: Option<Move>,
+    ) -> Option<(Score, BoundType)> {
+        let tb = self.tablebases.as_ref()?;
+
+        let pieces = board.occupied().count() as usize;
+        if pieces > self.tb_cardinality
+            || (pieces == self.tb_cardinality && depth.raw() < self.tb_probe_depth)
+        {
+            return None;
+        }
+
+        let zeroing = prev_move.is_some_and(|m| {
+            m.is_capture()
+                || m.is_promotion()
+                || board.piece_at(m.to()).is_some_and(|(p, _)| p == Piece::Pawn)
+        });
+        if !zeroing {
+            return None;
+        }
+
+        let wdl = tb.probe_wdl(board)?;
+        self.shared.tb_hits.fetch_add(1, Ordering::Relaxed);
+
+        // Cursed wins and blessed losses are nearly draws under the 50-move rule
+        let draw = if self.tb_use_rule50 { Wdl::CursedWin } else { Wdl::Draw };
+        Some(if wdl > draw {
+            (Score::tb_win_in(ply.raw()), BoundType::LowerBound)
+        } else if wdl < -draw {
+            (Score::tb_loss_in(ply.raw()), BoundType::UpperBound)
+        } else {
+            (Score::cp(2 * wdl as i32), BoundType::Exact)
+        })
+    }
+
     /// Create a helper searcher that shares TT but has own tables
     fn create_helper(&self) -> Self {
         Self {
@@ -284,13 +709,40 @@ impl Searcher {
             killers: KillerTable::new(),
             history: HistoryTable::new(),
             countermoves: CounterMoveTable::new(),
+            cont_history: ContinuationHistory::new(),
+            capture_history: CaptureHistory::new(),
+            stack: SearchStack::new(),
             correction: CorrectionHistoryTable::new(),
             time_manager: self.time_manager.clone(),
+            node_limit: self.node_limit,
+            mate_limit: self.mate_limit,
             stats: SearchStats::default(),
+            flushed_nodes: 0,
             best_move: None,
             pv: Vec::new(),
+            multi_pv: 1,
+            report_lines: 1,
+            pv_lines: Vec::new(),
+            root_moves: self.root_moves.clone(),
+            excluded_root_moves: Vec::new(),
             nnue: self.nnue.clone(),
-            position_history: self.position_history.clone(),
+            game_history: self.game_history.clone(),
+            positions: self.positions.clone(),
+            halfmove_clock: self.halfmove_clock,
+            castling_rooks: self.castling_rooks,
+            tablebases: self.tablebases.clone(),
+            tb_probe_depth: self.tb_probe_depth,
+            tb_use_rule50: self.tb_use_rule50,
+            tb_cardinality: self.tb_cardinality,
+            tb_root_score: None,
+            contempt: self.contempt,
+            analysis_contempt: self.analysis_contempt,
+            search_contempt: self.search_contempt,
+            skill: self.skill,
+            skill_rng: Prng::new(0),
+            stop_requested: false,
+            listener: Arc::clone(&self.listener),
+            next_heartbeat_ms: HEARTBEAT_MS,
             stable_move_count: 0,
             last_best_move: None,
             num_threads: 1,
@@ -299,15 +751,23 @@ impl Searcher {
     }
 
     /// Run the search with given limits (with Lazy SMP multi-threading)
+    ///
+    /// The stop and ponder flags are not touched on entry, so a `stop` or
+    /// `ponderhit` signalled from another thread before the search gets
+    /// going is still honoured. Set `shared.ponder` before calling to
+    /// search on the opponent's time.
     pub fn search(&mut self, limits: SearchLimits) -> SearchResult {
         // Reset state
-        self.shared.stop.store(false, Ordering::Relaxed);
         self.shared.total_nodes.store(0, Ordering::Relaxed);
+        self.shared.tb_hits.store(0, Ordering::Relaxed);
         self.stats = SearchStats::default();
+        self.flushed_nodes = 0;
         self.best_move = None;
         self.pv.clear();
+        self.pv_lines.clear();
         self.stable_move_count = 0;
         self.last_best_move = None;
+        self.next_heartbeat_ms = HEARTBEAT_MS;
         
         // Increment TT generation for new search
         self.shared.tt.new_search();
@@ -317,11 +777,45 @@ impl Searcher {
         
         // Age history scores (decay old data, keep some history)
         self.history.age();
+        self.cont_history.age();
+        self.capture_history.age();
+        self.stack.clear();
         
         // Configure time management
         self.time_manager = TimeManager::from_limits(&limits, self.board.turn());
         
-        let max_depth = limits.depth.unwrap_or(Depth::MAX);
+        // Restrict root moves (go searchmoves)
+        self.root_moves = limits.searchmoves.clone();
+        
+        // Node and mate limits are checked by the search itself
+        self.node_limit = limits.nodes;
+        self.mate_limit = limits.mate;
+
+        // Analysis shows draws as draws unless asked otherwise
+        self.search_contempt = if limits.infinite && !self.analysis_contempt {
+            0
+        } else {
+            self.contempt
+        };
+
+        // Search path starts at the root, after the game's positions
+        self.positions.reset(&self.game_history, self.board.hash(), self.halfmove_clock);
+
+        // Keep only the root moves that preserve the tablebase result
+        self.rank_root_tablebases();
+        
+        let mut max_depth = limits.depth.unwrap_or(Depth::MAX);
+
+        // Weakened play: shallow, node-capped search over several lines,
+        // one of which is picked at the end
+        let user_multi_pv = self.multi_pv;
+        self.report_lines = user_multi_pv;
+        if self.skill.enabled() {
+            let nodes = self.skill.node_limit();
+            self.node_limit = Some(self.node_limit.map_or(nodes, |n| n.min(nodes)));
+            max_depth = max_depth.min(Depth::new(self.skill.depth_limit()));
+            self.multi_pv = self.multi_pv.max(Skill::MULTI_PV);
+        }
         
         // Spawn helper threads for Lazy SMP
         let mut handles = Vec::new();
@@ -339,10 +833,18 @@ impl Searcher {
             }
         }
         
-        // Main thread search (prints UCI output)
-        let result = self.search_internal(limits, max_depth);
+        // Main thread search (reports to the listener)
+        let infinite = limits.infinite;
+        let mut result = self.search_internal(limits, max_depth);
+        
+        // An infinite or ponder search must not report until told to stop
+        // (or until ponderhit), even if it ran out of depth or found a mate
+        while (infinite || self.is_pondering()) && !self.shared.stop.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_millis(1));
+        }
         
         // Signal all helpers to stop
+        self.stop_requested = self.shared.stop.load(Ordering::Relaxed);
         self.shared.stop.store(true, Ordering::Relaxed);
         
         // Wait for all helper threads
@@ -350,8 +852,27 @@ impl Searcher {
             let _ = handle.join();
         }
         
+        // Clear the stop and ponder flags so the next search starts fresh
+        self.shared.stop.store(false, Ordering::Relaxed);
+        self.shared.ponder.store(false, Ordering::Relaxed);
+        
         // Get total nodes from all threads
         self.stats.nodes = self.shared.total_nodes.load(Ordering::Relaxed);
+
+        self.multi_pv = user_multi_pv;
+        if self.skill.enabled() {
+            if let Some(i) = self.skill.pick_best(&self.pv_lines, &mut self.skill_rng) {
+                let line = self.pv_lines[i].clone();
+                self.best_move = line.pv.first().copied();
+                self.pv = line.pv.clone();
+                result.best_move = self.best_move;
+                result.score = line.score;
+                result.pv = line.pv;
+            }
+        }
+
+        let ponder = self.ponder_move(&result);
+        self.listener.on_bestmove(&self.board, result.best_move, ponder);
         
         result
     }
@@ -359,12 +880,20 @@ impl Searcher {
     /// Internal search loop (called by main and helper threads)
     fn search_internal(&mut self, _limits: SearchLimits, max_depth: Depth) -> SearchResult {
         let mut best_score = Score::neg_infinity();
-        const INITIAL_WINDOW: i32 = 25;
         
         // Initialize evaluator at root
         let local_nnue = self.nnue.clone();
         let mut root_evaluator = SearchEvaluator::new(local_nnue.as_ref(), &self.board);
 
+        // Never ask for more lines than there are root moves
+        let root_move_count = if self.root_moves.is_empty() {
+            let board = &self.board;
+            board.generate_moves().iter().filter(|&m| self.castling_rooks.allows(board, m)).count()
+        } else {
+            self.root_moves.len()
+        };
+        let num_lines = self.multi_pv.min(root_move_count).max(1);
+
         for depth in 1..=max_depth.raw() {
             // Check if we can start a new iteration
             if !self.can_start_new_iteration() {
@@ -372,65 +901,61 @@ impl Searcher {
             }
             
             // Early termination: stop when forced mate is found (winning or losing)
-            // No point searching further if we've found a forced mate
-            if best_score.is_mate_score() && self.best_move.is_some() {
+            // No point searching further if we've found a forced mate.
+            // With `go mate N`, keep looking until a short enough mate is proven.
+            if self.mate_limit.is_some() {
+                if self.mate_limit_reached(best_score) && self.best_move.is_some() {
+                    break;
+                }
+            } else if best_score.is_mate_score() && self.best_move.is_some() {
                 break;
             }
 
-            // Aspiration window: use previous score +/- delta after depth 1
-            let mut delta = INITIAL_WINDOW;
-            let mut alpha = if depth > 1 && !best_score.is_mate() { 
-                best_score - Score::cp(delta) 
-            } else { 
-                Score::neg_infinity() 
-            };
-            let mut beta = if depth > 1 && !best_score.is_mate() { 
-                best_score + Score::cp(delta) 
-            } else { 
-                Score::infinity() 
-            };
+            // MultiPV: search the root once per line, excluding the moves
+            // already found for earlier lines at this depth
+            self.excluded_root_moves.clear();
+            let mut lines: Vec<PvLine> = Vec::with_capacity(num_lines);
+            for pv_idx in 0..num_lines {
+                let prev_score = self.pv_lines.get(pv_idx).map(|line| line.score);
 
-            // Aspiration loop: widen window on fail-high/low
-            loop {
-                let result = negamax::search::<Root>(
-                    self,
-                    &mut root_evaluator,
-                    &self.board.clone(),
-                    Depth::new(depth),
-                    Ply::ZERO,
-                    alpha,
-                    beta,
-                    None,  // No prev move at root
-                );
-
-                if self.should_stop() {
+                // Only exact scores become lines; a line cut off by `stop`
+                // ends the iteration
+                let Some(result) = self.aspiration_search(&mut root_evaluator, depth, pv_idx, prev_score) else {
                     break;
-                }
+                };
+                let Some(m) = result.best_move else {
+                    break;
+                };
 
-                // Check if score is within window
-                if result.score <= alpha {
-                    // Fail-low: widen alpha
-                    alpha = Score::neg_infinity();
-                } else if result.score >= beta {
-                    // Fail-high: widen beta
-                    beta = Score::infinity();
-                } else {
-                    // Score within window, accept result
-                    if let Some(m) = result.best_move {
-                        self.best_move = Some(m);
-                        best_score = result.score;
-                        self.pv = result.pv.clone();
-                    }
+                lines.push(PvLine { score: result.score, pv: result.pv, depth });
+                self.excluded_root_moves.push(m);
+            }
+            self.excluded_root_moves.clear();
+
+            // An iteration interrupted before its first line finished has
+            // nothing new; keep the previous one
+            if lines.is_empty() {
+                break;
+            }
+
+            // Later lines can occasionally outscore earlier ones; keep them
+            // ordered. Lines this iteration didn't reach keep their previous
+            // result (and depth) after the new ones.
+            lines.sort_by_key(|line| std::cmp::Reverse(line.score));
+            for line in std::mem::take(&mut self.pv_lines) {
+                if lines.len() >= num_lines {
                     break;
                 }
-
-                // Widen window for next attempt
-                delta *= 2;
-                if delta > 500 {
-                    alpha = Score::neg_infinity();
-                    beta = Score::infinity();
+                if !lines.iter().any(|l| l.pv.first() == line.pv.first()) {
+                    lines.push(line);
                 }
             }
+            self.pv_lines = lines;
+
+            let line = &self.pv_lines[0];
+            self.best_move = line.pv.first().copied();
+            best_score = line.score;
+            self.pv = line.pv.clone();
 
             self.stats.depth = Depth::new(depth);
             self.stats.hashfull = self.shared.tt.hashfull();
@@ -439,7 +964,7 @@ impl Searcher {
             self.stats.time_ms = self.time_manager.elapsed();
             
             // Report nodes to shared counter
-            self.shared.total_nodes.fetch_add(self.stats.nodes, Ordering::Relaxed);
+            self.flush_nodes();
             
             // Track move stability for early termination
             if self.best_move == self.last_best_move {
@@ -447,33 +972,30 @@ impl Searcher {
             } else {
                 self.stable_move_count = 0;
                 self.last_best_move = self.best_move;
+                self.stats.best_move_time_ms = self.stats.time_ms;
+                self.stats.best_move_depth = self.stats.depth;
             }
 
             // Print info for this depth (main thread only)
-            if !self.is_helper && !self.should_stop() {
-                self.stats.print_profiling();
+            if !self.is_helper {
                 self.stats.time_search = (self.time_manager.elapsed() as u64) * 1_000_000;
-                let pv_str: String = self.pv.iter()
-                    .map(|m| m.to_string())
-                    .collect::<Vec<_>>()
-                    .join(" ");
-                    
-                println!(
-                    "info depth {} seldepth {} score {} nodes {} qnodes {} evals {} nps {} time {} hashfull {} pv {}",
-                    depth,
-                    self.stats.seldepth.raw(),
-                    best_score,
-                    self.shared.total_nodes.load(Ordering::Relaxed),
-                    self.stats.qnodes,
-                    self.stats.eval_calls,
-                    self.stats.nps(),
-                    self.stats.time_ms,
-                    self.stats.hashfull,
-                    pv_str
-                );
+
+                for pv_idx in 0..self.pv_lines.len().min(self.report_lines) {
+                    let line = &self.pv_lines[pv_idx];
+                    // Report the tablebase result unless the search found a mate
+                    let score = match self.tb_root_score {
+                        Some(tb_score) if !line.score.is_mate_score() => tb_score,
+                        _ => line.score,
+                    };
+                    self.report_pv(line.depth, pv_idx, score, None, line.pv.clone());
+                }
+                self.listener.on_iteration(&self.board, &self.stats);
+                self.next_heartbeat_ms = self.stats.time_ms + HEARTBEAT_MS;
             }
         }
 
+        self.flush_nodes();
+
         SearchResult {
             best_move: self.best_move,
             score: best_score,
@@ -482,10 +1004,155 @@ impl Searcher {
         }
     }
 
+    /// Search the root at one depth inside an aspiration window around the
+    /// previous iteration's score for this line.
+    ///
+    /// Only a score inside the window (an exact score) is returned. `None`
+    /// means the search was stopped first, including in the middle of a
+    /// re-search after a fail high or low.
+    fn aspiration_search(
+        &mut self,
+        root_evaluator: &mut SearchEvaluator,
+        depth: i32,
+        pv_idx: usize,
+        prev_score: Option<Score>,
+    ) -> Option<SearchResult> {
+        const INITIAL_WINDOW: i32 = 25;
+
+        // Aspiration window: use previous score +/- delta after depth 1
+        let use_window = depth > 1 && prev_score.is_some_and(|s| !s.is_mate_score());
+        let center = prev_score.unwrap_or(Score::draw());
+        let mut delta = INITIAL_WINDOW;
+        let mut alpha = if use_window { 
+            center - Score::cp(delta) 
+        } else { 
+            Score::neg_infinity() 
+        };
+        let mut beta = if use_window { 
+            center + Score::cp(delta) 
+        } else { 
+            Score::infinity() 
+        };
+
+        // Aspiration loop: widen window on fail-high/low
+        loop {
+            let result = negamax::search::<Root>(
+                self,
+                root_evaluator,
+                &self.board.clone(),
+                Depth::new(depth),
+                Ply::ZERO,
+                alpha,
+                beta,
+                None,  // No prev move at root
+                None,
+            );
+
+            if self.should_stop() {
+                return None;
+            }
+
+            // Check if score is within window
+            let bound = if result.score <= alpha {
+                // Fail-low: widen alpha
+                alpha = Score::neg_infinity();
+                BoundType::UpperBound
+            } else if result.score >= beta {
+                // Fail-high: widen beta
+                beta = Score::infinity();
+                BoundType::LowerBound
+            } else {
+                // Score within window, accept result
+                return Some(result);
+            };
+
+            // Let the GUI see the score moving during long re-searches
+            if !self.is_helper && pv_idx < self.report_lines && self.time_manager.elapsed() >= INFO_DELAY_MS {
+                self.stats.time_ms = self.time_manager.elapsed();
+                self.report_pv(depth, pv_idx, result.score, Some(bound), result.pv.clone());
+            }
+
+            // Widen window for next attempt
+            delta *= 2;
+            if delta > 500 {
+                alpha = Score::neg_infinity();
+                beta = Score::infinity();
+            }
+        }
+    }
+
     /// Increment node counter
+    ///
+    /// Node-limited searches publish every node so the budget holds exactly
+    /// across threads; otherwise nodes are published in batches.
     #[inline]
     pub fn inc_nodes(&mut self) {
         self.stats.nodes += 1;
+        if self.node_limit.is_some() || self.stats.nodes & 1023 == 0 {
+            self.flush_nodes();
+        }
+        if self.stats.nodes & 16383 == 0 && !self.is_helper {
+            self.heartbeat();
+        }
+    }
+
+    /// Print a progress line if no info line went out for a while
+    fn heartbeat(&mut self) {
+        let elapsed = self.time_manager.elapsed();
+        if elapsed < self.next_heartbeat_ms {
+            return;
+        }
+        self.next_heartbeat_ms = elapsed + HEARTBEAT_MS;
+
+        let nodes = self.shared.total_nodes.load(Ordering::Relaxed);
+        self.listener.on_progress(&ProgressInfo {
+            nodes,
+            nps: nodes * 1000 / elapsed.max(1),
+            time_ms: elapsed,
+            hashfull: self.shared.tt.hashfull(),
+            tb_hits: self.shared.tb_hits.load(Ordering::Relaxed),
+        });
+    }
+
+    /// Report the root move being searched (main thread, after the first second)
+    pub fn report_currmove(&self, depth: Depth, m: Move, move_number: usize) {
+        if self.is_helper || self.time_manager.elapsed() < INFO_DELAY_MS {
+            return;
+        }
+        self.listener.on_currmove(&self.board, &CurrMoveInfo {
+            depth: depth.raw(),
+            mv: m,
+            number: move_number + self.excluded_root_moves.len(),
+        });
+    }
+
+    /// Report one root line. `bound` marks an aspiration fail-high
+    /// (`LowerBound`) or fail-low (`UpperBound`) score.
+    fn report_pv(&self, depth: i32, pv_idx: usize, score: Score, bound: Option<BoundType>, pv: Vec<Move>) {
+        let nodes = self.shared.total_nodes.load(Ordering::Relaxed);
+        self.listener.on_pv(&self.board, &PvInfo {
+            depth,
+            seldepth: self.stats.seldepth.raw(),
+            multipv: pv_idx + 1,
+            score,
+            bound,
+            nodes,
+            nps: nodes * 1000 / self.stats.time_ms.max(1),
+            time_ms: self.stats.time_ms,
+            hashfull: self.shared.tt.hashfull(),
+            tb_hits: self.shared.tb_hits.load(Ordering::Relaxed),
+            pv,
+        });
+    }
+
+    /// Add locally counted nodes to the shared node counter
+    #[inline]
+    fn flush_nodes(&mut self) {
+        let pending = self.stats.nodes - self.flushed_nodes;
+        if pending > 0 {
+            self.shared.total_nodes.fetch_add(pending, Ordering::Relaxed);
+            self.flushed_nodes = self.stats.nodes;
+        }
     }
 
     /// Update selective depth
@@ -535,3 +1202,51 @@ impl Default for Searcher {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopped_multipv_search_keeps_lines_consistent() {
+        let listener = Arc::new(CollectingListener::new());
+        let mut searcher = Searcher::with_hash_size(1);
+        searcher.set_listener(listener.clone());
+        searcher.set_multi_pv(3);
+        searcher.set_position(Board::default());
+
+        // The node budget runs out partway through an iteration
+        let mut limits = SearchLimits::depth(64);
+        limits.nodes = Some(30_000);
+        let result = searcher.search(limits);
+
+        let lines = searcher.pv_lines();
+        assert_eq!(lines.len(), 3);
+        for i in 0..lines.len() {
+            for j in i + 1..lines.len() {
+                assert_ne!(lines[i].pv[0], lines[j].pv[0]);
+            }
+        }
+        assert_eq!(result.best_move, Some(lines[0].pv[0]));
+
+        // Each reported iteration shows every root move once, and the
+        // bestmove sent is line 1
+        let events = listener.take();
+        let mut reported: Vec<Move> = Vec::new();
+        let mut bestmove = None;
+        for event in &events {
+            match event {
+                SearchEvent::Pv(info) if info.bound.is_none() => {
+                    if info.multipv == 1 {
+                        reported.clear();
+                    }
+                    assert!(!reported.contains(&info.pv[0]));
+                    reported.push(info.pv[0]);
+                }
+                SearchEvent::BestMove { best, .. } => bestmove = Some(*best),
+                _ => {}
+            }
+        }
+        assert_eq!(bestmove, Some(result.best_move));
+    }
+}
@@ -0,0 +1,367 @@
+//! Staged, lazy move picker.
+//!
+//! Hands out moves one at a time in the order the search wants to try
+//! them, doing only as much work as needed:
+//! 1. TT move, checked for legality without generating moves
+//! 2. Good captures and promotions (SEE >= 0), best MVV-LVA first
+//! 3. Killer moves
+//! 4. Counter-move
+//! 5. Quiet moves by history score
+//! 6. Bad captures, in the order they were found
+//!
+//! Moves are generated once, when the TT move didn't cut off, and each
+//! stage selects its next move on demand instead of sorting the list.
+
+use super::history::HistoryContext;
+use super::ordering::mvv_lva_score;
+use super::see;
+use crate::types::{piece_value, Bitboard, Board, Color, Move, MoveFlag, Piece, Square};
+use crate::types::castling::{is_castling, CastlingRooks};
+use movegen::attacks::{bishop_attacks, king_attacks, knight_attacks, pawn_attacks, rook_attacks};
+use std::time::Instant;
+
+/// Maximum number of legal moves in a chess position (218) with headroom
+const MAX_MOVES: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    TtMove,
+    Generate,
+    GoodCaptures,
+    Killer1,
+    Killer2,
+    CounterMove,
+    ScoreQuiets,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Yields the legal moves of a position, best guesses first
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    counter_move: Option<Move>,
+    /// Rooks allowed to castle (movegen may castle with another rook)
+    castling_rooks: CastlingRooks,
+    /// Captures and promotions in `[0, noisy_end)`, quiets in `[noisy_end, len)`
+    moves: [Move; MAX_MOVES],
+    scores: [i32; MAX_MOVES],
+    len: usize,
+    noisy_end: usize,
+    /// Next unpicked move of the current stage
+    cur: usize,
+    /// Bad captures are moved to `[0, bad_end)` as good captures are picked
+    bad_end: usize,
+    /// Time spent generating moves (ns)
+    pub time_gen: u64,
+    /// Time spent scoring moves (ns)
+    pub time_order: u64,
+}
+
+impl MovePicker {
+    pub fn new(tt_move: Option<Move>, killers: [Option<Move>; 2], counter_move: Option<Move>) -> Self {
+        Self {
+            stage: Stage::TtMove,
+            tt_move,
+            killers,
+            counter_move,
+            castling_rooks: CastlingRooks::default(),
+            moves: [Move::NULL; MAX_MOVES],
+            scores: [0; MAX_MOVES],
+            len: 0,
+            noisy_end: 0,
+            cur: 0,
+            bad_end: 0,
+            time_gen: 0,
+            time_order: 0,
+        }
+    }
+
+    /// Only hand out castling moves with the rooks holding the rights
+    pub fn with_castling_rooks(mut self, castling_rooks: CastlingRooks) -> Self {
+        self.castling_rooks = castling_rooks;
+        self
+    }
+
+    /// Next move to search, or `None` once every legal move was returned.
+    /// Each legal move is returned exactly once.
+    pub fn next(&mut self, board: &Board, history: &HistoryContext) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TtMove => {
+                    self.stage = Stage::Generate;
+                    if let Some(m) = self.tt_move.filter(|&m| is_legal(board, m) && self.castling_rooks.allows(board, m)) {
+                        return Some(m);
+                    }
+                    // Not legal here (e.g. a hash collision): don't skip it later
+                    self.tt_move = None;
+                }
+                Stage::Generate => {
+                    self.generate(board, history);
+                    self.cur = 0;
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    while self.cur < self.noisy_end {
+                        let m = self.pick_best(self.noisy_end);
+                        if Some(m) == self.tt_move {
+                            continue;
+                        }
+                        if is_good_noisy(board, m) {
+                            return Some(m);
+                        }
+                        self.moves[self.bad_end] = m;
+                        self.bad_end += 1;
+                    }
+                    self.stage = Stage::Killer1;
+                }
+                Stage::Killer1 | Stage::Killer2 => {
+                    let index = if self.stage == Stage::Killer1 { 0 } else { 1 };
+                    self.stage = if index == 0 { Stage::Killer2 } else { Stage::CounterMove };
+                    let killer = self.killers[index].filter(|_| index == 0 || self.killers[1] != self.killers[0]);
+                    if let Some(m) = killer.filter(|&m| self.is_new_quiet(m)) {
+                        return Some(m);
+                    }
+                }
+                Stage::CounterMove => {
+                    self.stage = Stage::ScoreQuiets;
+                    if let Some(m) = self.counter_move
+                        .filter(|&m| !self.killers.contains(&Some(m)) && self.is_new_quiet(m))
+                    {
+                        return Some(m);
+                    }
+                }
+                Stage::ScoreQuiets => {
+                    let t_order = Instant::now();
+                    for i in self.noisy_end..self.len {
+                        self.scores[i] = history.quiet(board, self.moves[i]);
+                    }
+                    self.time_order += t_order.elapsed().as_nanos() as u64;
+                    self.cur = self.noisy_end;
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    while self.cur < self.len {
+                        let m = self.pick_best(self.len);
+                        let seen = Some(m) == self.tt_move
+                            || self.killers.contains(&Some(m))
+                            || Some(m) == self.counter_move;
+                        if !seen {
+                            return Some(m);
+                        }
+                    }
+                    self.cur = 0;
+                    self.stage = Stage::BadCaptures;
+                }
+                Stage::BadCaptures => {
+                    if self.cur < self.bad_end {
+                        self.cur += 1;
+                        return Some(self.moves[self.cur - 1]);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Generate all legal moves, noisy ones first, and score the noisy ones
+    fn generate(&mut self, board: &Board, history: &HistoryContext) {
+        let t_gen = Instant::now();
+        let list = board.generate_moves();
+        self.time_gen += t_gen.elapsed().as_nanos() as u64;
+
+        let t_order = Instant::now();
+        let mut quiets = [Move::NULL; MAX_MOVES];
+        let mut quiet_count = 0;
+        for m in list.iter().take(MAX_MOVES) {
+            if !self.castling_rooks.allows(board, m) {
+                continue;
+            }
+            if m.is_capture() || m.is_promotion() {
+                let promotion = m.flag().promotion_piece().map_or(0, piece_value);
+                self.scores[self.len] = mvv_lva_score(board, m) + promotion + history.capture(board, m) / 8;
+                self.moves[self.len] = m;
+                self.len += 1;
+            } else {
+                quiets[quiet_count] = m;
+                quiet_count += 1;
+            }
+        }
+        self.noisy_end = self.len;
+        self.moves[self.len..self.len + quiet_count].copy_from_slice(&quiets[..quiet_count]);
+        self.len += quiet_count;
+        self.time_order += t_order.elapsed().as_nanos() as u64;
+    }
+
+    /// Selection sort step: move the best-scored move of `[cur, end)` to
+    /// `cur` and return it
+    fn pick_best(&mut self, end: usize) -> Move {
+        let mut best = self.cur;
+        for i in self.cur + 1..end {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.moves.swap(self.cur, best);
+        self.scores.swap(self.cur, best);
+        self.cur += 1;
+        self.moves[self.cur - 1]
+    }
+
+    /// Check if `m` is a generated quiet move not returned as the TT move
+    fn is_new_quiet(&self, m: Move) -> bool {
+        Some(m) != self.tt_move && self.moves[self.noisy_end..self.len].contains(&m)
+    }
+}
+
+/// Captures that don't lose material and queen promotions are searched
+/// early; the rest wait until after the quiet moves
+fn is_good_noisy(board: &Board, m: Move) -> bool {
+    if m.is_capture() {
+        see::see_ge(board, m, 0)
+    } else {
+        m.flag().promotion_piece() == Some(Piece::Queen)
+    }
+}
+
+/// Check if a move (typically from the TT, which may hold a move of a
+/// different position after a hash collision) is legal on `board`,
+/// without generating moves
+pub fn is_legal(board: &Board, m: Move) -> bool {
+    if m == Move::NULL {
+        return false;
+    }
+    let us = board.turn();
+    let (from, to) = (m.from(), m.to());
+    let Some((piece, color)) = board.piece_at(from) else {
+        return false;
+    };
+    if color != us {
+        return false;
+    }
+
+    // Castling legality depends on attacked squares and the rook: rare
+    // enough to look it up in the generated moves
+    if is_castling(m) {
+        return board.generate_moves().iter().any(|legal| legal == m);
+    }
+
+    // The flag must agree with what's on the target square
+    let target = board.piece_at(to);
+    if target.is_some_and(|(p, c)| c == us || p == Piece::King) {
+        return false;
+    }
+    if m.flag() == MoveFlag::EnPassant {
+        if piece != Piece::Pawn || board.ep_square() != Some(to) {
+            return false;
+        }
+    } else if m.is_capture() != target.is_some() {
+        return false;
+    }
+    let last_rank = if us == Color::White { 7 } else { 0 };
+    if m.is_promotion() != (piece == Piece::Pawn && to.rank().index() == last_rank) {
+        return false;
+    }
+
+    let occupied = board.occupied();
+    let to_bb = Bitboard::from_square(to);
+    let reachable = match piece {
+        Piece::Pawn if m.is_capture() => (pawn_attacks(us, from) & to_bb).any(),
+        Piece::Pawn => {
+            let (step, start_rank) = if us == Color::White { (8, 1) } else { (-8, 6) };
+            let forward = |sq: Square| Square::from_index((sq.index() as i32 + step) as u8);
+            match forward(from) {
+                Some(single) if m.flag() == MoveFlag::DoublePawnPush => {
+                    from.rank().index() == start_rank
+                        && board.piece_at(single).is_none()
+                        && forward(single) == Some(to)
+                }
+                Some(single) => single == to,
+                None => false,
+            }
+        }
+        _ if !matches!(m.flag(), MoveFlag::Quiet | MoveFlag::Capture) => false,
+        Piece::Knight => (knight_attacks(from) & to_bb).any(),
+        Piece::Bishop => (bishop_attacks(from, occupied) & to_bb).any(),
+        Piece::Rook => (rook_attacks(from, occupied) & to_bb).any(),
+        Piece::Queen => ((bishop_attacks(from, occupied) | rook_attacks(from, occupied)) & to_bb).any(),
+        Piece::King => (king_attacks(from) & to_bb).any(),
+    };
+    if !reachable {
+        return false;
+    }
+
+    // Our king must not be left in check
+    let after = board.make_move_new(m);
+    !see::is_attacked(&after, after.king_square(us), !us, after.occupied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{CaptureHistory, ContinuationHistory, HistoryTable};
+
+    /// Every legal move is picked exactly once, TT move first
+    #[test]
+    fn test_picks_each_move_once() {
+        let (history, cont_history, capture_history) =
+            (HistoryTable::new(), ContinuationHistory::new(), CaptureHistory::new());
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let ctx = HistoryContext {
+                history: &history,
+                cont_history: &cont_history,
+                capture_history: &capture_history,
+                prev: [None; 2],
+                color: board.turn(),
+            };
+            let legal: Vec<Move> = board.generate_moves().iter().collect();
+            let tt_move = legal.last().copied();
+            let mut picker = MovePicker::new(tt_move, [legal.first().copied(), None], None);
+
+            let mut picked = Vec::new();
+            while let Some(m) = picker.next(&board, &ctx) {
+                picked.push(m);
+            }
+            assert_eq!(picked.first().copied(), tt_move, "{}", fen);
+            assert_eq!(picked.len(), legal.len(), "{}", fen);
+            assert!(legal.iter().all(|m| picked.contains(m)), "{}", fen);
+            assert!(legal.iter().all(|&m| is_legal(&board, m)), "{}", fen);
+        }
+    }
+    /// Castling with a rook that doesn't hold the right is never picked
+    #[test]
+    fn test_skips_castling_with_wrong_rook() {
+        let (history, cont_history, capture_history) =
+            (HistoryTable::new(), ContinuationHistory::new(), CaptureHistory::new());
+        // The right is on the a1 rook; movegen only knows "Q" and castles with b1
+        let fen = "4k3/8/8/8/8/8/8/RR2K3 w A - 0 1";
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/RR2K3 w Q - 0 1").unwrap();
+        let ctx = HistoryContext {
+            history: &history,
+            cont_history: &cont_history,
+            capture_history: &capture_history,
+            prev: [None; 2],
+            color: board.turn(),
+        };
+        let castle = board.generate_moves().iter().find(|&m| is_castling(m)).unwrap();
+        let mut picker = MovePicker::new(Some(castle), [Some(castle), None], None)
+            .with_castling_rooks(CastlingRooks::from_fen(fen));
+
+        let mut picked = Vec::new();
+        while let Some(m) = picker.next(&board, &ctx) {
+            picked.push(m);
+        }
+        assert!(!picked.contains(&castle));
+        assert_eq!(picked.len(), board.generate_moves().len() - 1);
+    }
+}
@@ -3,16 +3,18 @@
 //! This is the core search algorithm with:
 //! - Transposition table probing and storing
 //! - Alpha-beta pruning
+//! - Singular extensions and multi-cut
 //! - Quiescence search for captures
 //! - Compile-time node type specialization (no runtime PV checks)
 //!
 //! Uses Rust generics for compile-time node type specialization.
 //! See `node_types` module for `NodeType` trait and concrete types.
 
-use super::{Searcher, SearchStats, ordering, qsearch, see};
+use super::{Searcher, SearchStats, PieceTo, movepicker, qsearch, see};
+use super::movepicker::MovePicker;
 use super::node_types::{NodeType, OffPV};
 use super::tt::BoundType;
-use crate::types::{Board, Move, Score, Depth, Ply, Piece, SCORE_MATE};
+use crate::types::{Board, Move, Score, Depth, Ply, Piece, SCORE_MATE, MAX_DEPTH};
 use crate::eval::SearchEvaluator;
 use std::time::Instant;
 
@@ -31,6 +33,11 @@ pub struct SearchResult {
 /// - `NT::PV`: true if this is a principal variation node
 /// - `NT::ROOT`: true if this is the root node
 /// - `NT::Next`: the node type for child PV searches
+///
+/// `excluded` skips one move (the singular extension search): the node is
+/// then only partially searched, so it neither cuts off on the TT nor
+/// stores its result there.
+#[allow(clippy::too_many_arguments)]
 pub fn search<NT: NodeType>(
     searcher: &mut Searcher,
     evaluator: &mut SearchEvaluator,
@@ -40,36 +47,20 @@ pub fn search<NT: NodeType>(
     mut alpha: Score,
     mut beta: Score,
     prev_move: Option<Move>,
+    excluded: Option<Move>,
 ) -> SearchResult {
     searcher.inc_nodes();
     searcher.update_seldepth(ply);
 
     let hash = board.hash();
 
-    // === Repetition Detection with Contempt ===
-    // Check for draw by repetition (position seen before in game history)
-    // Use contempt: avoid draws when winning, seek draws when losing
-    // Skip at root node (ply == 0)
-    if !NT::ROOT && searcher.is_repetition(hash) {
-        // Contempt factor: small penalty/bonus for draws based on expected score
-        // If alpha > 0 (we expect to be winning), penalize draws to avoid them
-        // If beta < 0 (we expect to be losing), reward draws to seek them
-        const CONTEMPT: i32 = 10; // Small contempt factor (centipawns)
-        
-        let draw_score = if alpha.raw() > CONTEMPT {
-            // We're winning - penalize draws to avoid repetition
-            Score::cp(-CONTEMPT)
-        } else if beta.raw() < -CONTEMPT {
-            // We're losing - reward draws to seek repetition  
-            Score::cp(CONTEMPT)
-        } else {
-            // Close to equal - treat as pure draw
-            Score::draw()
-        };
-        
+    // === Draw Detection with Contempt ===
+    // Repetitions (in the game or the search path), the fifty-move rule and
+    // insufficient material. Skip at root node (ply == 0)
+    if !NT::ROOT && searcher.is_draw(board, ply) {
         return SearchResult {
             best_move: None,
-            score: draw_score,
+            score: searcher.draw_score(ply),
             pv: Vec::new(),
             stats: searcher.stats().clone(),
         };
@@ -105,13 +96,23 @@ pub fn search<NT: NodeType>(
 
     let orig_alpha = alpha;
     let mut tt_move: Option<Move> = None;
+    let mut tt_eval: Option<Score> = None;
+    let mut tt_hit = None;
+
+    // Root searches restricted by `go searchmoves` or skipping the moves of
+    // earlier MultiPV lines don't cover every root move, so the TT entry for
+    // the root position doesn't describe them
+    let root_restricted = NT::ROOT
+        && (!searcher.root_moves.is_empty() || !searcher.excluded_root_moves.is_empty());
 
     // === TT Probe ===
     if let Some(entry) = searcher.shared.tt.probe(hash) {
         tt_move = entry.best_move();
+        tt_eval = entry.eval();
+        tt_hit = Some(entry);
         
         // Only use TT score if depth is sufficient
-        if entry.depth() >= depth {
+        if entry.depth() >= depth && !root_restricted && excluded.is_none() {
             let tt_score = entry.score().from_tt(ply.raw());
             
             match entry.bound() {
@@ -151,6 +152,34 @@ pub fn search<NT: NodeType>(
         }
     }
 
+    // === Tablebase Probe ===
+    // Cut off with the WDL result when it is exact or outside the window
+    if !NT::ROOT && excluded.is_none() {
+        if let Some((tb_score, bound)) = searcher.probe_tablebases(board, depth, ply, prev_move) {
+            let cutoff = match bound {
+                BoundType::LowerBound => tb_score >= beta,
+                BoundType::UpperBound => tb_score <= alpha,
+                _ => true,
+            };
+            if cutoff {
+                searcher.shared.tt.store(
+                    hash,
+                    None,
+                    tb_score.to_tt(ply.raw()),
+                    Score::none(),
+                    Depth::new((depth.raw() + 6).min(MAX_DEPTH - 1)),
+                    bound,
+                );
+                return SearchResult {
+                    best_move: None,
+                    score: tb_score,
+                    pv: Vec::new(),
+                    stats: searcher.stats().clone(),
+                };
+            }
+        }
+    }
+
     // Check for stop condition
     if searcher.should_stop() {
         return SearchResult {
@@ -167,14 +196,23 @@ pub fn search<NT: NodeType>(
     // If we are way ahead, we can prune without searching
     // Distinct from standard Futility Pruning which prunes *moves*
     let mut static_eval = None;
+    let mut raw_static_eval = None;
     let pawn_hash = board.pawn_hash();
     let color = board.turn();
     
     if !in_check && depth.raw() <= 7 {
-        searcher.inc_eval_calls();
-        let t_eval = Instant::now();
-        let raw_eval = evaluator.evaluate(board);
-        searcher.add_eval_time(t_eval.elapsed().as_nanos() as u64);
+        // Reuse the static eval cached in the TT when we have one
+        let raw_eval = match tt_eval {
+            Some(eval) => eval,
+            None => {
+                searcher.inc_eval_calls();
+                let t_eval = Instant::now();
+                let eval = evaluator.evaluate(board);
+                searcher.add_eval_time(t_eval.elapsed().as_nanos() as u64);
+                eval
+            }
+        };
+        raw_static_eval = Some(raw_eval);
         
         // Apply correction history adjustment
         let correction = searcher.correction.get(color, pawn_hash);
@@ -184,7 +222,9 @@ pub fn search<NT: NodeType>(
         // RFP Margin: 90 * depth (tuned)
         let margin = Score::cp(90 * depth.raw() as i32);
         
-        if eval - margin >= beta {
+        // Not in a singular verification search: a static cutoff there
+        // would skip every move and fake a multi-cut
+        if excluded.is_none() && eval - margin >= beta {
              return SearchResult {
                 best_move: None,
                 score: eval - margin, // Soft cap to avoid crazy scores
@@ -197,7 +237,7 @@ pub fn search<NT: NodeType>(
     // === ProbCut ===
     // Only on non-PV nodes (zero-window)
     const PROBCUT_MARGIN: i32 = 100;
-    if !NT::PV && depth.raw() >= 5 && !in_check && beta.raw().abs() < (SCORE_MATE - 1000) {
+    if !NT::PV && depth.raw() >= 5 && !in_check && excluded.is_none() && beta.raw().abs() < (SCORE_MATE - 1000) {
         let probe_beta = beta + Score::cp(PROBCUT_MARGIN);
         let probe_depth = Depth::new(depth.raw() - 4);
 
@@ -209,7 +249,8 @@ pub fn search<NT: NodeType>(
             ply,
             probe_beta - Score::cp(1),
             probe_beta,
-            None
+            None,
+            None,
         );
 
         if result.score >= probe_beta {
@@ -225,7 +266,7 @@ pub fn search<NT: NodeType>(
     // === Null Move Pruning ===
     // Skip if: in check, depth too low, PV node, or only king+pawns
     // Note: we don't do NMP on PV nodes or at root
-    if !NT::PV && !in_check && depth.raw() >= 3 {
+    if !NT::PV && !in_check && depth.raw() >= 3 && excluded.is_none() {
         // Don't do null move in pure pawn endgames (zugzwang risk)
         let dominated_by_pawns = (board.piece_bb(Piece::Knight)
             | board.piece_bb(Piece::Bishop)
@@ -242,6 +283,8 @@ pub fn search<NT: NodeType>(
             // Clone evaluator for null move (no piece updates needed)
             let mut null_evaluator = evaluator.clone();
             
+            searcher.positions.push_null(null_board.hash());
+            searcher.stack.set(ply, None);
             let null_result = search::<OffPV>(
                 searcher,
                 &mut null_evaluator,
@@ -251,7 +294,9 @@ pub fn search<NT: NodeType>(
                 -beta,
                 -beta + Score::cp(1),
                 None,  // No prev move for null move
+                None,
             );
+            searcher.positions.pop();
             
             let null_score = -null_result.score;
             
@@ -281,33 +326,21 @@ pub fn search<NT: NodeType>(
             alpha,
             beta,
             prev_move,
+            excluded,
         );
         
         tt_move = result.best_move;
     }
 
-    // Generate legal moves
-    let t_gen = Instant::now();
-    let mut moves = board.generate_moves();
-    searcher.add_gen_time(t_gen.elapsed().as_nanos() as u64);
-
-    // Check for checkmate or stalemate
-    if moves.is_empty() {
-        let score = if board.in_check() {
-            Score::mated_in(ply.raw())
-        } else {
-            Score::draw()
-        };
-        return SearchResult {
-            best_move: None,
-            score,
-            pv: Vec::new(),
-            stats: searcher.stats().clone(),
-        };
-    }
-
-    // Quiescence search at depth 0
+    // Quiescence search at depth 0. Quiescence only looks at captures, so
+    // check for checkmate and stalemate first
     if depth.is_qs() {
+        let t_gen = Instant::now();
+        let no_moves = board.generate_moves().is_empty();
+        searcher.add_gen_time(t_gen.elapsed().as_nanos() as u64);
+        if no_moves {
+            return no_moves_result(searcher, board, ply);
+        }
         return qsearch::quiescence::<NT>(searcher, evaluator, board, ply, 0, alpha, beta);
     }
 
@@ -318,11 +351,6 @@ pub fn search<NT: NodeType>(
     // Get counter-move for opponent's previous move
     let counter_move = prev_move.and_then(|pm| searcher.countermoves.get(pm));
 
-    // Order moves (TT, killers, counter-move, and history)
-    let t_order = Instant::now();
-    ordering::order_moves_full(board, moves.as_slice_mut(), tt_move, killers, counter_move, &searcher.history, color);
-    searcher.add_order_time(t_order.elapsed().as_nanos() as u64);
-
     // Static eval is already computed for RFP if depth <= 7
     // If not (e.g. was in check check or deeper), compute it now if needed for Razoring/Futility
     if static_eval.is_none() && depth.raw() <= 3 && !in_check {
@@ -333,8 +361,8 @@ pub fn search<NT: NodeType>(
         static_eval = Some(val);
     }
     
-    // Razoring - only on non-PV nodes
-    if !NT::PV && depth.raw() <= 3 && !in_check {
+    // Razoring - only on non-PV nodes, and not in a singular verification search
+    if !NT::PV && depth.raw() <= 3 && !in_check && excluded.is_none() {
         if let Some(eval) = static_eval {
             let threshold = alpha - Score::cp(200 + depth.raw() as i32 * 60);
             if eval < threshold {
@@ -346,14 +374,89 @@ pub fn search<NT: NodeType>(
         }
     }
 
+    // === Singular Extension ===
+    // If the TT move is a cutoff at nearly full depth and every other move
+    // fails well below its score at reduced depth, the TT move is the only
+    // good one: extend it. If another move also beats that lowered bound
+    // and the bound is above beta, several moves cut off here (multi-cut).
+    let mut singular_extension = 0;
+    if let (Some(entry), Some(tm)) = (tt_hit, tt_move) {
+        let tt_score = entry.score().from_tt(ply.raw());
+        if !NT::ROOT
+            && excluded.is_none()
+            && depth.raw() >= 8
+            && ply.raw() < MAX_DEPTH
+            && entry.bound() == BoundType::LowerBound
+            && entry.depth().raw() >= depth.raw() - 3
+            && !tt_score.is_mate_score()
+            && movepicker::is_legal(board, tm)
+            && searcher.castling_rooks.allows(board, tm)
+        {
+            let singular_beta = tt_score - Score::cp(2 * depth.raw());
+            let singular_depth = Depth::new((depth.raw() - 1) / 2);
+
+            let result = search::<OffPV>(
+                searcher,
+                evaluator,
+                board,
+                singular_depth,
+                ply,
+                singular_beta - Score::cp(1),
+                singular_beta,
+                prev_move,
+                Some(tm),
+            );
+
+            if result.score < singular_beta {
+                singular_extension = 1;
+            } else if singular_beta >= beta {
+                return SearchResult {
+                    best_move: tt_move,
+                    score: singular_beta,
+                    pv: Vec::new(),
+                    stats: searcher.stats().clone(),
+                };
+            }
+        }
+    }
+
     let mut best_move = None;
     let mut best_score = Score::neg_infinity();
     let mut pv = Vec::new();
     // Use fixed-size array for searched quiets to avoid allocations
     let mut searched_quiets: [Move; 64] = [Move::NULL; 64];
     let mut quiets_count = 0usize;
+    let mut searched_captures: [Move; 32] = [Move::NULL; 32];
+    let mut captures_count = 0usize;
+
+    // Root move restrictions: `go searchmoves` and MultiPV exclusions
+    let (allowed, skipped) = if root_restricted {
+        (searcher.root_moves.clone(), searcher.excluded_root_moves.clone())
+    } else {
+        (Vec::new(), Vec::new())
+    };
+    let searchable = |m: &Move| {
+        (allowed.is_empty() || allowed.contains(m)) && !skipped.contains(m) && Some(*m) != excluded
+    };
+
+    // Moves come lazily: TT move, good captures, killers, counter-move,
+    // quiets by history, bad captures
+    let mut picker = MovePicker::new(tt_move, killers, counter_move).with_castling_rooks(searcher.castling_rooks);
+    let mut legal_moves = 0usize;
+    let mut searchable_moves = 0usize;
+
+    while let Some(m) = picker.next(board, &searcher.history_context(color, ply)) {
+        legal_moves += 1;
+        if !searchable(&m) {
+            continue;
+        }
+        let move_idx = searchable_moves;
+        searchable_moves += 1;
+
+        if NT::ROOT {
+            searcher.report_currmove(depth, m, move_idx + 1);
+        }
 
-    for (move_idx, m) in moves.iter().enumerate() {
         let new_board = board.make_move_new(m);
 
         // Prefetch TT entry for next position
@@ -365,6 +468,12 @@ pub fn search<NT: NodeType>(
         let is_killer = killers[0] == Some(m) || killers[1] == Some(m);
         let is_quiet = !is_capture && !is_promotion;
         let gives_check = new_board.in_check();
+        // Butterfly + continuation history, for LMR and history pruning
+        let quiet_history = if is_quiet {
+            searcher.history_context(color, ply).quiet(board, m)
+        } else {
+            0
+        };
 
         // === Late Move Pruning (LMP) ===
         // If we have searched enough quiet moves at low depth, stop searching the rest.
@@ -381,8 +490,15 @@ pub fn search<NT: NodeType>(
         // Reduce depth for late quiet moves that aren't special
         let mut reduced = false;
         
-        // Check extension: extend +1 when in check to avoid horizon effect
-        let extension = if in_check { 1 } else { 0 };
+        // Check extension: extend +1 when in check to avoid horizon effect;
+        // a singular TT move is extended the same way
+        let extension = if in_check {
+            1
+        } else if Some(m) == tt_move {
+            singular_extension
+        } else {
+            0
+        };
         
         let search_depth = if move_idx >= 2 
             && depth.raw() >= 3 
@@ -395,6 +511,8 @@ pub fn search<NT: NodeType>(
             let d = (depth.raw() as f32).ln();
             let m_idx = ((move_idx + 1) as f32).ln();
             let reduction = ((d * m_idx) / 1.9) as i32;
+            // Reduce moves with a good history less, bad ones more
+            let reduction = reduction - quiet_history / 8192;
             let reduction = reduction.min(depth.raw() - 2).max(1);
             reduced = true;
             Depth::new((depth.raw() - 1 - reduction + extension).max(1))
@@ -407,7 +525,7 @@ pub fn search<NT: NodeType>(
         if depth.raw() < 4 && is_quiet && !in_check && !gives_check && !is_killer && move_idx > 0 {
             // Threshold: -3000 * depth (e.g. -3000 at d1, -6000 at d2)
             let threshold = -3000 * depth.raw() as i32;
-            if searcher.history.get(color, m) < threshold {
+            if quiet_history < threshold {
                  // Track for history stats if needed, or just prune
                 continue;
             }
@@ -443,6 +561,9 @@ pub fn search<NT: NodeType>(
         // === Principal Variation Search (PVS) ===
         let mut result;
         let mut score;
+
+        searcher.positions.push_move(board, m, new_board.hash());
+        searcher.stack.set(ply, PieceTo::of_move(board, m));
         
         if move_idx == 0 {
             // Incremental update for next depth
@@ -461,6 +582,7 @@ pub fn search<NT: NodeType>(
                 -beta,
                 -alpha,
                 Some(m),  // Pass current move as prev_move
+                None,
             );
             score = -result.score;
         } else {
@@ -480,6 +602,7 @@ pub fn search<NT: NodeType>(
                 -alpha - Score::cp(1),
                 -alpha,
                 Some(m),
+                None,
             );
             score = -result.score;
             
@@ -495,6 +618,7 @@ pub fn search<NT: NodeType>(
                     -beta,
                     -alpha,
                     Some(m),
+                    None,
                 );
                 score = -result.score;
             }
@@ -516,10 +640,13 @@ pub fn search<NT: NodeType>(
                 -beta,
                 -alpha,
                 Some(m),
+                None,
             );
             score = -result.score;
         }
 
+        searcher.positions.pop();
+
         if searcher.should_stop() {
             break;
         }
@@ -540,27 +667,54 @@ pub fn search<NT: NodeType>(
                     if is_quiet {
                         searcher.killers.store(ply, m);
                         // Update history: bonus for cutoff move, penalty for searched quiets
-                        searcher.history.update_on_cutoff(color, m, depth.raw(), &searched_quiets[..quiets_count]);
+                        let quiets = &searched_quiets[..quiets_count];
+                        searcher.history.update_on_cutoff(color, m, depth.raw(), quiets);
+                        let prev = searcher.stack.continuation(ply);
+                        searcher.cont_history.update_on_cutoff(board, prev, m, depth.raw(), quiets);
                         // Update counter-move
                         if let Some(pm) = prev_move {
                             searcher.countermoves.store(pm, m);
                         }
                     }
+                    // Captures that didn't cut off lose score either way
+                    let captures = &searched_captures[..captures_count];
+                    searcher.capture_history.update_on_cutoff(board, m, depth.raw(), captures);
                     break;
                 }
             }
         }
         
-        // Track searched quiet moves for history penalty
+        // Track searched quiet moves and captures for history penalty
         if is_quiet && quiets_count < 64 {
             searched_quiets[quiets_count] = m;
             quiets_count += 1;
+        } else if is_capture && captures_count < 32 {
+            searched_captures[captures_count] = m;
+            captures_count += 1;
         }
     }
 
+    searcher.add_gen_time(picker.time_gen);
+    searcher.add_order_time(picker.time_order);
+
+    // Checkmate or stalemate
+    if legal_moves == 0 {
+        return no_moves_result(searcher, board, ply);
+    }
+
+    // Every other move was pruned in a singular search: fail low
+    if excluded.is_some() && best_move.is_none() {
+        return SearchResult {
+            best_move: None,
+            score: alpha,
+            pv: Vec::new(),
+            stats: searcher.stats().clone(),
+        };
+    }
+
     // === Update Correction History ===
     // Track difference between static eval and search score to correct future evals
-    if let Some(se) = static_eval {
+    if let Some(se) = static_eval.filter(|_| excluded.is_none()) {
         if !best_score.is_mate_score() && !se.is_mate_score() {
             let diff = best_score.raw() - se.raw();
             searcher.correction.update(color, pawn_hash, depth.raw(), diff);
@@ -568,7 +722,7 @@ pub fn search<NT: NodeType>(
     }
 
     // === TT Store ===
-    if !searcher.should_stop() {
+    if !searcher.should_stop() && !root_restricted && excluded.is_none() {
         let bound = if best_score >= beta {
             BoundType::LowerBound
         } else if best_score > orig_alpha {
@@ -581,6 +735,7 @@ pub fn search<NT: NodeType>(
             hash,
             best_move,
             best_score.to_tt(ply.raw()),
+            raw_static_eval.unwrap_or(Score::none()),
             depth,
             bound,
         );
@@ -593,3 +748,18 @@ pub fn search<NT: NodeType>(
         stats: searcher.stats().clone(),
     }
 }
+
+/// Result of a node without legal moves: mated if in check, else stalemate
+fn no_moves_result(searcher: &Searcher, board: &Board, ply: Ply) -> SearchResult {
+    let score = if board.in_check() {
+        Score::mated_in(ply.raw())
+    } else {
+        searcher.draw_score(ply)
+    };
+    SearchResult {
+        best_move: None,
+        score,
+        pv: Vec::new(),
+        stats: searcher.stats().clone(),
+    }
+}
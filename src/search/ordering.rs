@@ -4,7 +4,7 @@
 //! Uses lazy selection sort to avoid full sort overhead.
 
 use crate::types::{Board, Move, Color, piece_value};
-use super::history::HistoryTable;
+use super::history::{CaptureHistory, ContinuationHistory, HistoryContext, HistoryTable};
 use super::see;
 
 /// Move score constants
@@ -18,7 +18,7 @@ const BAD_CAPTURE_PENALTY: i32 = -10_000;
 
 /// MVV-LVA scores for capture ordering
 #[inline]
-fn mvv_lva_score(board: &Board, m: Move) -> i32 {
+pub(super) fn mvv_lva_score(board: &Board, m: Move) -> i32 {
     let victim = board.piece_at(m.to()).map(|(p, _)| p);
     let attacker = board.piece_at(m.from()).map(|(p, _)| p);
 
@@ -38,8 +38,7 @@ pub fn score_move(
     tt_move: Option<Move>,
     killers: [Option<Move>; 2],
     counter_move: Option<Move>,
-    history: &HistoryTable,
-    color: Color,
+    history: &HistoryContext,
 ) -> i32 {
     // TT move is always searched first
     if tt_move == Some(m) {
@@ -53,7 +52,8 @@ pub fn score_move(
         score += piece_value(promo) + PROMOTION_BONUS;
     }
 
-    // Captures: skip SEE for obviously good captures (victim >= attacker)
+    // Captures: skip SEE for obviously good captures (victim >= attacker).
+    // Capture history breaks ties between captures of similar MVV-LVA
     if m.is_capture() {
         let mvv_lva = mvv_lva_score(board, m);
         if mvv_lva >= 0 {
@@ -68,6 +68,7 @@ pub fn score_move(
                 score += BAD_CAPTURE_PENALTY + mvv_lva;
             }
         }
+        score += history.capture(board, m) / 8;
     } else {
         // Quiet move - check killers and counter-move
         if killers[0] == Some(m) {
@@ -77,8 +78,9 @@ pub fn score_move(
         } else if counter_move == Some(m) {
             score += COUNTER_MOVE_BONUS;
         } else {
-            // Use history score for other quiet moves
-            score += history.get(color, m);
+            // Use butterfly + continuation history for other quiet moves,
+            // kept below the counter-move
+            score += history.quiet(board, m).min(COUNTER_MOVE_BONUS - 1);
         }
     }
 
@@ -92,15 +94,14 @@ pub fn order_moves_full(
     tt_move: Option<Move>,
     killers: [Option<Move>; 2],
     counter_move: Option<Move>,
-    history: &HistoryTable,
-    color: Color,
+    history: &HistoryContext,
 ) {
     // Score moves in place
     let mut scores: [i32; 256] = [0; 256];
     let count = moves.len().min(256);
     
     for i in 0..count {
-        scores[i] = score_move(board, moves[i], tt_move, killers, counter_move, history, color);
+        scores[i] = score_move(board, moves[i], tt_move, killers, counter_move, history);
     }
     
     // Selection sort by scores (in-place, no allocation)
@@ -129,8 +130,16 @@ pub fn order_moves_with_tt_and_killers(
     tt_move: Option<Move>,
     killers: [Option<Move>; 2],
 ) {
-    let dummy_history = HistoryTable::new();
-    order_moves_full(board, moves, tt_move, killers, None, &dummy_history, Color::White);
+    let (history, cont_history, capture_history) =
+        (HistoryTable::new(), ContinuationHistory::new(), CaptureHistory::new());
+    let dummy_history = HistoryContext {
+        history: &history,
+        cont_history: &cont_history,
+        capture_history: &capture_history,
+        prev: [None; 2],
+        color: Color::White,
+    };
+    order_moves_full(board, moves, tt_move, killers, None, &dummy_history);
 }
 
 #[allow(dead_code)]
@@ -58,6 +58,14 @@ fn get_piece_attacks(board: &Board, target: movegen::Square, piece: Piece, side:
     }
 }
 
+/// Check if any piece of `side` attacks a square
+#[inline]
+pub fn is_attacked(board: &Board, sq: movegen::Square, side: Color, occupied: Bitboard) -> bool {
+    [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King]
+        .into_iter()
+        .any(|piece| get_piece_attacks(board, sq, piece, side, occupied).any())
+}
+
 /// Static Exchange Evaluation
 /// Returns the material balance after a capture sequence.
 /// Uses fixed-size array to avoid allocations.
@@ -0,0 +1,111 @@
+//! Playing strength limitation (`Skill Level`).
+//!
+//! A weakened search is capped in depth and nodes, always searches several
+//! root lines (MultiPV) and then picks among them with a random bonus that
+//! grows as the level drops, so lower levels play plausible but inferior
+//! moves instead of random ones. The scheme follows Stockfish's.
+//!
+//! Levels run from 0 to 20 (full strength). They have not been measured
+//! against rated opponents, so no Elo is claimed for them and the engine
+//! offers no `UCI_LimitStrength`/`UCI_Elo`; a lower level is only known to
+//! play weaker.
+//!
+//! A weakened search looks at `MULTI_PV` lines but reports only as many as
+//! the `MultiPV` option asks for.
+
+use super::PvLine;
+use crate::types::{Prng, Score};
+
+/// Strength setting for one search
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skill {
+    /// 0.0 (weakest) to 20.0 (full strength)
+    level: f64,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self { level: Self::MAX_LEVEL as f64 }
+    }
+}
+
+impl Skill {
+    /// Full strength
+    pub const MAX_LEVEL: i32 = 20;
+    /// Root lines a weakened search chooses from
+    pub const MULTI_PV: usize = 4;
+
+    pub fn from_level(level: i32) -> Self {
+        Self { level: level.clamp(0, Self::MAX_LEVEL) as f64 }
+    }
+
+    /// Check if this setting weakens play at all
+    pub fn enabled(&self) -> bool {
+        self.level < Self::MAX_LEVEL as f64
+    }
+
+    /// Deepest iteration searched
+    pub fn depth_limit(&self) -> i32 {
+        1 + self.level as i32
+    }
+
+    /// Node budget: 2000 nodes at level 0, doubling every two levels
+    pub fn node_limit(&self) -> u64 {
+        (2000.0 * 2f64.powf(self.level / 2.0)) as u64
+    }
+
+    /// Choose which of the best-first `lines` to play.
+    ///
+    /// Each line gets a bonus of its deficit to the best line scaled by
+    /// the weakness, plus a random share of the spread between the lines
+    /// (at most a pawn). Returns `None` if there are no lines.
+    pub fn pick_best(&self, lines: &[PvLine], rng: &mut Prng) -> Option<usize> {
+        let top = lines.first()?.score.raw();
+        let worst = lines.last()?.score.raw();
+        let delta = (top - worst).min(Score::cp(100).raw()) as i64;
+        let weakness = (120.0 - 2.0 * self.level) as i64;
+
+        let mut best = 0;
+        let mut max_score = i64::MIN;
+        for (i, line) in lines.iter().enumerate() {
+            let score = line.score.raw() as i64;
+            let push = (weakness * (top as i64 - score) + delta * rng.below(weakness as u64) as i64) / 128;
+            if score + push >= max_score {
+                max_score = score + push;
+                best = i;
+            }
+        }
+        Some(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skill_levels() {
+        assert!(!Skill::default().enabled());
+        assert!(!Skill::from_level(25).enabled());
+        assert!(Skill::from_level(0).enabled());
+        assert_eq!(Skill::from_level(5).depth_limit(), 6);
+        assert!(Skill::from_level(3).node_limit() < Skill::from_level(10).node_limit());
+        assert!(Skill::from_level(Skill::MAX_LEVEL - 1).enabled());
+    }
+
+    #[test]
+    fn test_pick_best_prefers_better_lines() {
+        let lines: Vec<PvLine> = [50, 40, -300, -500]
+            .iter()
+            .map(|&cp| PvLine { score: Score::cp(cp), pv: Vec::new(), depth: 1 })
+            .collect();
+
+        // A strong setting never plays a line that loses several pawns
+        let skill = Skill::from_level(19);
+        let mut rng = Prng::new(1);
+        for _ in 0..100 {
+            assert!(skill.pick_best(&lines, &mut rng).unwrap() < 2);
+        }
+        assert_eq!(skill.pick_best(&[], &mut rng), None);
+    }
+}
@@ -0,0 +1,56 @@
+//! Per-ply search stack.
+//!
+//! Records the piece moved at each ply of the current search path, so a
+//! node can look up the moves played 1 and 2 plies before it for
+//! continuation history.
+
+use super::history::PieceTo;
+use crate::types::{Ply, MAX_PLY};
+
+/// Moved piece per ply: entry `p` is the move made at the node at ply `p`
+/// (`None` for a null move or before the move is known)
+#[derive(Clone)]
+pub struct SearchStack {
+    moved: [Option<PieceTo>; MAX_PLY as usize],
+}
+
+impl SearchStack {
+    pub fn new() -> Self {
+        Self {
+            moved: [None; MAX_PLY as usize],
+        }
+    }
+
+    /// Record the move about to be searched at `ply`
+    #[inline]
+    pub fn set(&mut self, ply: Ply, moved: Option<PieceTo>) {
+        if let Some(entry) = self.moved.get_mut(ply.raw() as usize) {
+            *entry = moved;
+        }
+    }
+
+    /// Moves played 1 and 2 plies before the node at `ply`
+    #[inline]
+    pub fn continuation(&self, ply: Ply) -> [Option<PieceTo>; 2] {
+        let back = |n: i32| {
+            let p = ply.raw() - n;
+            if p < 0 {
+                None
+            } else {
+                self.moved.get(p as usize).copied().flatten()
+            }
+        };
+        [back(1), back(2)]
+    }
+
+    /// Forget the previous search path
+    pub fn clear(&mut self) {
+        self.moved = [None; MAX_PLY as usize];
+    }
+}
+
+impl Default for SearchStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
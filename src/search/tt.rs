@@ -4,11 +4,13 @@
 //! that stores search results to avoid redundant computation.
 //!
 //! # Design
-//! - 8-byte entries packed into AtomicU64 for lock-free access
-//! - Depth-preferred replacement with age-based eviction
-//! - Lock-free for Lazy SMP multi-threading support
+//! - 64-byte buckets (one cache line) of four 16-byte entries
+//! - Entry data packed into AtomicU64, key stored XOR-ed with the data
+//!   so torn writes are detected (lock-free for Lazy SMP)
+//! - Replacement weighs depth, age and bound type
+//! - Entries also cache the static eval so it needn't be recomputed
 
-use crate::types::{Move, Score, Depth, Hash};
+use crate::types::{Move, Score, Depth, Hash, SCORE_NONE};
 use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 
 /// Type of bound stored in TT entry
@@ -38,20 +40,22 @@ impl From<u8> for BoundType {
 
 /// A single entry in the transposition table.
 ///
-/// Packed into 8 bytes (64 bits) for atomic access:
-/// - key: 16 bits (upper bits of hash for verification)
+/// The entry data is packed into 8 bytes (64 bits) for atomic access:
 /// - best_move: 16 bits (encoded move)
 /// - score: 16 bits
+/// - eval: 16 bits (static evaluation, `SCORE_NONE` if not computed)
 /// - depth: 8 bits
 /// - bound_and_age: 8 bits (bound type in low 2 bits, age in high 6 bits)
+///
+/// The position key is stored next to it in the table slot (see `TTSlot`).
 #[derive(Debug, Clone, Copy, Default)]
 pub struct TTEntry {
-    /// Upper 16 bits of Zobrist hash for verification
-    key: u16,
     /// Best move found (encoded)
     best_move: u16,
-    /// Evaluation score
+    /// Search score
     score: i16,
+    /// Static evaluation of the position
+    eval: i16,
     /// Search depth
     depth: i8,
     /// Bound type (2 bits) + generation/age (6 bits)
@@ -61,29 +65,29 @@ pub struct TTEntry {
 impl TTEntry {
     /// Create a new TT entry
     pub fn new(
-        hash: Hash,
         best_move: Option<Move>,
         score: Score,
+        eval: Score,
         depth: Depth,
         bound: BoundType,
         generation: u8,
     ) -> Self {
         Self {
-            key: (hash >> 48) as u16,
             best_move: encode_move(best_move),
             score: score.raw() as i16,
+            eval: eval.raw() as i16,
             depth: depth.raw() as i8,
             bound_and_age: (bound as u8) | ((generation & 0x3F) << 2),
         }
     }
     
     /// Pack entry into a u64 for atomic storage
-    /// Layout: key(16) | best_move(16) | score(16) | depth(8) | bound_and_age(8)
+    /// Layout: best_move(16) | score(16) | eval(16) | depth(8) | bound_and_age(8)
     #[inline]
     pub fn to_u64(&self) -> u64 {
-        ((self.key as u64) << 48)
-            | ((self.best_move as u64) << 32)
-            | (((self.score as u16) as u64) << 16)
+        ((self.best_move as u64) << 48)
+            | (((self.score as u16) as u64) << 32)
+            | (((self.eval as u16) as u64) << 16)
             | ((self.depth as u8 as u64) << 8)
             | (self.bound_and_age as u64)
     }
@@ -92,20 +96,14 @@ impl TTEntry {
     #[inline]
     pub fn from_u64(raw: u64) -> Self {
         Self {
-            key: (raw >> 48) as u16,
-            best_move: (raw >> 32) as u16,
-            score: (raw >> 16) as i16,
+            best_move: (raw >> 48) as u16,
+            score: (raw >> 32) as i16,
+            eval: (raw >> 16) as i16,
             depth: (raw >> 8) as i8,
             bound_and_age: raw as u8,
         }
     }
 
-    /// Check if entry matches the given hash
-    #[inline]
-    pub fn matches(&self, hash: Hash) -> bool {
-        self.key == (hash >> 48) as u16
-    }
-
     /// Get the bound type
     #[inline]
     pub fn bound(&self) -> BoundType {
@@ -124,6 +122,17 @@ impl TTEntry {
         Score::cp(self.score as i32)
     }
 
+    /// Get the static evaluation, if one was stored
+    #[inline]
+    pub fn eval(&self) -> Option<Score> {
+        let eval = self.eval as i32;
+        if eval == SCORE_NONE {
+            None
+        } else {
+            Some(Score::cp(eval))
+        }
+    }
+
     /// Get the depth
     #[inline]
     pub fn depth(&self) -> Depth {
@@ -141,6 +150,17 @@ impl TTEntry {
     pub fn is_empty(&self) -> bool {
         self.bound() == BoundType::None
     }
+
+    /// How much this entry is worth keeping (lower = replaced first).
+    ///
+    /// Deep entries are worth more, entries from older searches lose value
+    /// quickly, and exact bounds get a small bonus over one-sided bounds.
+    #[inline]
+    fn replacement_value(&self, generation: u8) -> i32 {
+        let age = (generation.wrapping_sub(self.generation()) & 0x3F) as i32;
+        let exact_bonus = if self.bound() == BoundType::Exact { 2 } else { 0 };
+        self.depth as i32 - 8 * age + exact_bonus
+    }
 }
 
 /// Encode a move into 16 bits
@@ -160,10 +180,53 @@ fn decode_move(encoded: u16) -> Option<Move> {
     Some(Move::from_bits(encoded))
 }
 
-/// Lock-free Transposition Table using AtomicU64
+/// One lock-free table slot.
+///
+/// The key is stored XOR-ed with the entry data, so a slot torn by two
+/// threads writing at once fails verification instead of returning data
+/// that belongs to another position.
+#[derive(Default)]
+struct TTSlot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl TTSlot {
+    /// Load the slot as (key, data)
+    #[inline]
+    fn load(&self) -> (Hash, u64) {
+        let data = self.data.load(Ordering::Relaxed);
+        let key = self.key.load(Ordering::Relaxed) ^ data;
+        (key, data)
+    }
+
+    #[inline]
+    fn store(&self, hash: Hash, data: u64) {
+        self.key.store(hash ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn clear(&self) {
+        self.key.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Number of entries per bucket (4 x 16 bytes = one 64-byte cache line)
+const BUCKET_SIZE: usize = 4;
+
+/// A cache-line-sized group of slots sharing one table index
+#[derive(Default)]
+#[repr(C, align(64))]
+struct Bucket {
+    slots: [TTSlot; BUCKET_SIZE],
+}
+
+/// Lock-free Transposition Table of cache-line-sized buckets
 pub struct TranspositionTable {
-    /// Table entries as atomic u64 values
-    entries: Vec<AtomicU64>,
+    /// Table buckets (each one cache line)
+    buckets: Vec<Bucket>,
     /// Current generation (incremented each new search)
     generation: AtomicU8,
     /// Size in MB (for reporting)
@@ -177,19 +240,17 @@ unsafe impl Sync for TranspositionTable {}
 impl TranspositionTable {
     /// Create a new TT with given size in MB
     pub fn new(size_mb: usize) -> Self {
-        // TTEntry is 8 bytes
-        let entry_size = 8;
-        let num_entries = (size_mb * 1024 * 1024) / entry_size;
-        // Round to power of 2 for fast modulo
-        let num_entries = num_entries.next_power_of_two() / 2;
-        let num_entries = num_entries.max(1024); // Minimum 1024 entries
-
-        let entries = (0..num_entries)
-            .map(|_| AtomicU64::new(0))
+        // Buckets are 64 bytes; any count works since indexing doesn't
+        // rely on a power-of-2 size
+        let num_buckets = (size_mb * 1024 * 1024) / std::mem::size_of::<Bucket>();
+        let num_buckets = num_buckets.max(256); // Minimum 1024 entries
+
+        let buckets = (0..num_buckets)
+            .map(|_| Bucket::default())
             .collect();
 
         Self {
-            entries,
+            buckets,
             generation: AtomicU8::new(0),
             size_mb,
         }
@@ -198,13 +259,13 @@ impl TranspositionTable {
     /// Get the number of entries
     #[inline]
     pub fn len(&self) -> usize {
-        self.entries.len()
+        self.buckets.len() * BUCKET_SIZE
     }
 
     /// Check if table is empty
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+        self.buckets.is_empty()
     }
 
     /// Get size in MB
@@ -215,7 +276,7 @@ impl TranspositionTable {
     /// Get current generation
     #[inline]
     pub fn generation(&self) -> u8 {
-        self.generation.load(Ordering::Relaxed)
+        self.generation.load(Ordering::Relaxed) & 0x3F
     }
 
     /// Increment generation (call at start of each search)
@@ -224,64 +285,90 @@ impl TranspositionTable {
         self.generation.fetch_add(1, Ordering::Relaxed);
     }
 
-    /// Get index for a hash
+    /// Get bucket index for a hash
     #[inline]
     fn index(&self, hash: Hash) -> usize {
-        // Fast modulo for power-of-2 size
-        (hash as usize) & (self.entries.len() - 1)
+        // Multiply-high maps the hash uniformly onto [0, len)
+        (((hash as u128) * (self.buckets.len() as u128)) >> 64) as usize
     }
 
     /// Probe the TT for an entry (lock-free)
     #[inline]
     pub fn probe(&self, hash: Hash) -> Option<TTEntry> {
-        let raw = self.entries[self.index(hash)].load(Ordering::Relaxed);
-        if raw == 0 {
-            return None;
-        }
-        
-        let entry = TTEntry::from_u64(raw);
-        if entry.matches(hash) && !entry.is_empty() {
-            Some(entry)
-        } else {
-            None
-        }
+        let bucket = &self.buckets[self.index(hash)];
+        bucket.slots.iter().find_map(|slot| {
+            let (key, data) = slot.load();
+            if data != 0 && key == hash {
+                Some(TTEntry::from_u64(data))
+            } else {
+                None
+            }
+        })
     }
 
     /// Store an entry in the TT (lock-free)
     ///
-    /// Uses depth-preferred replacement with age consideration
+    /// Reuses the slot already holding this position (or an empty slot),
+    /// otherwise replaces the least valuable entry of the bucket by depth,
+    /// age and bound type.
     /// Takes &self - uses atomic operations for thread-safety
     pub fn store(
         &self,
         hash: Hash,
         best_move: Option<Move>,
         score: Score,
+        eval: Score,
         depth: Depth,
         bound: BoundType,
     ) {
-        let idx = self.index(hash);
-        let existing_raw = self.entries[idx].load(Ordering::Relaxed);
-        let existing = TTEntry::from_u64(existing_raw);
+        let bucket = &self.buckets[self.index(hash)];
         let gen = self.generation();
 
-        // Replacement strategy:
-        // 1. Always replace empty entries
-        // 2. Always replace entries from older generations
-        // 3. Replace if new depth >= existing depth
-        let should_replace = existing.is_empty()
-            || existing.generation() != gen
-            || depth.raw() >= existing.depth.into();
-
-        if should_replace {
-            let new_entry = TTEntry::new(hash, best_move, score, depth, bound, gen);
-            self.entries[idx].store(new_entry.to_u64(), Ordering::Relaxed);
+        let mut target = 0;
+        let mut target_value = i32::MAX;
+        for (i, slot) in bucket.slots.iter().enumerate() {
+            let (key, data) = slot.load();
+
+            if data == 0 {
+                // Empty slot: nothing to lose
+                target = i;
+                break;
+            }
+
+            let existing = TTEntry::from_u64(data);
+            if key == hash {
+                // Same position: keep a deeper result from this search
+                // unless the new one is exact
+                if bound != BoundType::Exact
+                    && existing.generation() == gen
+                    && depth.raw() + 4 <= existing.depth().raw()
+                {
+                    return;
+                }
+                // Keep the old move if we didn't find one this time
+                let best_move = best_move.or(existing.best_move());
+                let entry = TTEntry::new(best_move, score, eval, depth, bound, gen);
+                slot.store(hash, entry.to_u64());
+                return;
+            }
+
+            let value = existing.replacement_value(gen);
+            if value < target_value {
+                target_value = value;
+                target = i;
+            }
         }
+
+        let new_entry = TTEntry::new(best_move, score, eval, depth, bound, gen);
+        bucket.slots[target].store(hash, new_entry.to_u64());
     }
 
     /// Clear the table
     pub fn clear(&self) {
-        for entry in &self.entries {
-            entry.store(0, Ordering::Relaxed);
+        for bucket in &self.buckets {
+            for slot in &bucket.slots {
+                slot.clear();
+            }
         }
         self.generation.store(0, Ordering::Relaxed);
     }
@@ -289,23 +376,31 @@ impl TranspositionTable {
     /// Get hashfull in permill (for UCI info)
     pub fn hashfull(&self) -> u32 {
         let gen = self.generation();
-        // Sample first 1000 entries
-        let sample_size = self.entries.len().min(1000);
-        let used = self.entries[..sample_size]
+        // Sample the first 1000 entries
+        let sample_buckets = self.buckets.len().min(1000 / BUCKET_SIZE);
+        let used = self.buckets[..sample_buckets]
             .iter()
-            .filter(|e| {
-                let entry = TTEntry::from_u64(e.load(Ordering::Relaxed));
+            .flat_map(|b| b.slots.iter())
+            .filter(|slot| {
+                let entry = TTEntry::from_u64(slot.load().1);
                 !entry.is_empty() && entry.generation() == gen
             })
             .count();
-        ((used * 1000) / sample_size) as u32
+        ((used * 1000) / (sample_buckets * BUCKET_SIZE)) as u32
     }
 
-    /// Prefetch entry for a hash (performance optimization)
+    /// Prefetch the bucket for a hash into cache (performance optimization)
     #[inline]
     pub fn prefetch(&self, hash: Hash) {
-        let _ = self.index(hash);
-        // Future: use platform-specific prefetch intrinsics
+        #[cfg(target_arch = "x86_64")]
+        {
+            use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+            let ptr = &self.buckets[self.index(hash)] as *const Bucket as *const i8;
+            // Safety: the pointer is to a live bucket; prefetch never faults
+            unsafe { _mm_prefetch::<_MM_HINT_T0>(ptr) };
+        }
+        #[cfg(not(target_arch = "x86_64"))]
+        let _ = hash;
     }
 }
 
@@ -329,12 +424,38 @@ mod tests {
         assert!(tt.probe(hash).is_none());
 
         // Store and retrieve
-        tt.store(hash, None, Score::cp(100), Depth::new(5), BoundType::Exact);
+        tt.store(hash, None, Score::cp(100), Score::cp(42), Depth::new(5), BoundType::Exact);
 
         let entry = tt.probe(hash).expect("Entry should exist");
         assert_eq!(entry.score().raw(), 100);
+        assert_eq!(entry.eval(), Some(Score::cp(42)));
         assert_eq!(entry.depth().raw(), 5);
         assert_eq!(entry.bound(), BoundType::Exact);
+
+        // A different position in the same bucket doesn't match
+        assert!(tt.probe(hash ^ 1).is_none());
+    }
+
+    #[test]
+    fn test_bucket_replacement() {
+        let tt = TranspositionTable::new(1);
+        // Hashes differing only in the low bits share a bucket
+        let base: Hash = 0x123456789ABC0000;
+
+        // Fill the bucket with depths 1..=4
+        for i in 0..BUCKET_SIZE as u64 {
+            tt.store(base + i, None, Score::cp(0), Score::none(), Depth::new(i as i32 + 1), BoundType::LowerBound);
+        }
+        for i in 0..BUCKET_SIZE as u64 {
+            assert!(tt.probe(base + i).is_some(), "entry {} should still be stored", i);
+        }
+
+        // A new position evicts the shallowest entry
+        tt.store(base + 10, None, Score::cp(0), Score::none(), Depth::new(8), BoundType::Exact);
+        assert!(tt.probe(base + 10).is_some());
+        assert!(tt.probe(base).is_none());
+        assert!(tt.probe(base + 3).is_some());
+        assert_eq!(tt.probe(base + 3).unwrap().eval(), None);
     }
 
     #[test]
@@ -353,9 +474,9 @@ mod tests {
     #[test]
     fn test_entry_pack_unpack() {
         let entry = TTEntry::new(
-            0xABCD123456789000,
             None,
-            Score::cp(150),
+            Score::cp(-150),
+            Score::cp(-120),
             Depth::new(8),
             BoundType::LowerBound,
             5,
@@ -364,8 +485,8 @@ mod tests {
         let packed = entry.to_u64();
         let unpacked = TTEntry::from_u64(packed);
         
-        assert_eq!(entry.key, unpacked.key);
         assert_eq!(entry.score, unpacked.score);
+        assert_eq!(entry.eval, unpacked.eval);
         assert_eq!(entry.depth, unpacked.depth);
         assert_eq!(entry.bound(), unpacked.bound());
         assert_eq!(entry.generation(), unpacked.generation());
@@ -0,0 +1,490 @@
+//! Syzygy endgame tablebase support.
+//!
+//! This module provides a pure-Rust prober for Syzygy WDL (`.rtbw`) and
+//! DTZ (`.rtbz`) tables with up to 7 pieces.
+//!
+//! # Usage
+//!
+//! ```ignore
+//! use chessinrust::syzygy::Tablebases;
+//!
+//! let tb = Tablebases::load("/path/to/syzygy");
+//! let board = Board::from_fen("8/8/8/4k3/8/8/8/R3K3 w - - 0 1").unwrap();
+//!
+//! // Win/draw/loss and distance to zeroing for the side to move
+//! let wdl = tb.probe_wdl(&board);
+//! let dtz = tb.probe_dtz(&board);
+//! ```
+
+mod table;
+
+pub use table::{Wdl, TB_PIECES};
+
+use table::{Lookup, Table, TableInfo, TableKind};
+use crate::types::{Board, Color, Move, Piece, Score, MAX_PLY};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+
+const PIECE_CHARS: [char; 6] = ['P', 'N', 'B', 'R', 'Q', 'K'];
+
+/// Piece counts indexed by `[color][piece]`
+type MaterialCounts = [[usize; 6]; 2];
+
+/// Material key: four bits per (color, piece) count
+fn material_key(counts: &MaterialCounts) -> u64 {
+    let mut key = 0;
+    for (c, pieces) in counts.iter().enumerate() {
+        for (p, &n) in pieces.iter().enumerate() {
+            key |= (n as u64 & 0xF) << ((c * 6 + p) * 4);
+        }
+    }
+    key
+}
+
+fn board_counts(board: &Board) -> MaterialCounts {
+    let mut counts = [[0; 6]; 2];
+    for color in [Color::White, Color::Black] {
+        for piece in [Piece::Pawn, Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen, Piece::King] {
+            counts[color.index()][piece.index()] =
+                (board.piece_bb(piece) & board.color_bb(color)).count() as usize;
+        }
+    }
+    counts
+}
+
+impl TableInfo {
+    /// Parse a table name such as `KRvK` or `KPPvKR`
+    fn from_name(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let mut counts: MaterialCounts = [[0; 6]; 2];
+        for (c, side) in [white, black].iter().enumerate() {
+            for ch in side.chars() {
+                let p = PIECE_CHARS.iter().position(|&pc| pc == ch)?;
+                counts[c][p] += 1;
+            }
+            if counts[c][Piece::King.index()] != 1 {
+                return None;
+            }
+        }
+
+        let piece_count: usize = counts.iter().flatten().sum();
+        if piece_count > TB_PIECES {
+            return None;
+        }
+
+        let (white_pawns, black_pawns) = (counts[0][0], counts[1][0]);
+        // The leading colour is the one with fewer (but some) pawns
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] };
+
+        Some(Self {
+            key: material_key(&counts),
+            key2: material_key(&[counts[1], counts[0]]),
+            piece_count,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: counts.iter().any(|side| side[..5].contains(&1)),
+            pawn_count,
+        })
+    }
+}
+
+/// A table pair found on disk, opened on first use
+struct TableEntry {
+    info: TableInfo,
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+impl TableEntry {
+    fn table(&self, kind: TableKind) -> Option<&Table> {
+        let (cell, path) = match kind {
+            TableKind::Wdl => (&self.wdl, Some(&self.wdl_path)),
+            TableKind::Dtz => (&self.dtz, self.dtz_path.as_ref()),
+        };
+        cell.get_or_init(|| path.and_then(|p| Table::open(p, kind, &self.info).ok()))
+            .as_ref()
+    }
+}
+
+/// Rank of a certain win: above any DTZ plus fifty-move counter a table
+/// can hold, so cursed wins and blessed losses never reach zero
+pub const MAX_DTZ: i32 = 1 << 18;
+
+/// Rank of a root move whose DTZ, counted from the root, is `dtz`.
+/// Certain wins rank equally; wins and losses the fifty-move rule may turn
+/// into draws rank by how close the draw is.
+fn dtz_rank(dtz: i32, cnt50: i32, repeated: bool) -> i32 {
+    if dtz > 0 {
+        if dtz + cnt50 <= 99 && !repeated { MAX_DTZ } else { MAX_DTZ - (dtz + cnt50) }
+    } else if dtz < 0 {
+        if -dtz * 2 + cnt50 < 100 { -MAX_DTZ } else { -MAX_DTZ + (-dtz + cnt50) }
+    } else {
+        0
+    }
+}
+
+/// Score to report for a root move of rank `rank`. With the fifty-move rule,
+/// spoilt wins and losses get small scores that shrink as the draw nears.
+fn rank_score(rank: i32, use_rule50: bool) -> Score {
+    let bound = if use_rule50 { MAX_DTZ - 100 } else { 1 };
+    if rank >= bound {
+        Score::tb_win_in(MAX_PLY)
+    } else if rank > 0 {
+        Score::cp((rank - (MAX_DTZ - 200)).max(3) * 100 / 200)
+    } else if rank == 0 {
+        Score::draw()
+    } else if rank > -bound {
+        Score::cp((rank + (MAX_DTZ - 200)).min(-3) * 100 / 200)
+    } else {
+        Score::tb_loss_in(MAX_PLY)
+    }
+}
+
+/// Tablebase rank of a root move (higher is better)
+#[derive(Debug, Clone, Copy)]
+pub struct RankedMove {
+    pub mv: Move,
+    /// `MAX_DTZ` for a win, `-MAX_DTZ` for a loss, 0 for a draw; wins and
+    /// losses the fifty-move rule may spoil rank in between
+    pub rank: i32,
+    /// Score to report for the move
+    pub score: Score,
+}
+
+/// A set of Syzygy tables
+pub struct Tablebases {
+    entries: HashMap<u64, Arc<TableEntry>>,
+    num_tables: usize,
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    /// Find the tables in a list of directories separated like `PATH`
+    /// (`:` on Unix, `;` on Windows). Files are opened lazily.
+    pub fn load(paths: &str) -> Self {
+        let mut found: HashMap<String, (Option<PathBuf>, Option<PathBuf>)> = HashMap::new();
+
+        for dir in std::env::split_paths(paths) {
+            let Ok(read_dir) = std::fs::read_dir(&dir) else { continue };
+            for path in read_dir.flatten().map(|e| e.path()) {
+                let (Some(stem), Some(ext)) = (path.file_stem(), path.extension()) else { continue };
+                let name = stem.to_string_lossy().to_string();
+                let slot = found.entry(name).or_default();
+                if ext == TableKind::Wdl.extension() {
+                    slot.0.get_or_insert(path);
+                } else if ext == TableKind::Dtz.extension() {
+                    slot.1.get_or_insert(path);
+                }
+            }
+        }
+
+        let mut tb = Self { entries: HashMap::new(), num_tables: 0, max_pieces: 0 };
+        for (name, (wdl_path, dtz_path)) in found {
+            let (Some(wdl_path), Some(info)) = (wdl_path, TableInfo::from_name(&name)) else { continue };
+            if tb.entries.contains_key(&info.key) {
+                continue;
+            }
+
+            tb.num_tables += 1;
+            tb.max_pieces = tb.max_pieces.max(info.piece_count);
+            let (key, key2) = (info.key, info.key2);
+            let entry = Arc::new(TableEntry {
+                info,
+                wdl_path,
+                dtz_path,
+                wdl: OnceLock::new(),
+                dtz: OnceLock::new(),
+            });
+            tb.entries.insert(key2, Arc::clone(&entry));
+            tb.entries.insert(key, entry);
+        }
+        tb
+    }
+
+    /// Number of WDL tables found
+    pub fn len(&self) -> usize {
+        self.num_tables
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_tables == 0
+    }
+
+    /// Largest number of pieces of any table found
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Win/draw/loss for the side to move, ignoring the fifty-move counter.
+    ///
+    /// Returns `None` if the position is not covered by the tables, or has
+    /// castling rights (which tables don't encode).
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        if has_castling_rights(board) {
+            return None;
+        }
+        self.search(board, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Distance to zeroing (capture or pawn move) in plies, signed by the
+    /// WDL result: positive when winning, negative when losing, 0 for draws.
+    ///
+    /// Cursed wins and blessed losses are offset by 100 plies.
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if has_castling_rights(board) {
+            return None;
+        }
+
+        let (wdl, zeroing_best) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+        // The best move is zeroing: the table value is a "don't care"
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        if let Lookup::Value(dtz) = self.probe_table(board, TableKind::Dtz, wdl)? {
+            let cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl_sign(wdl));
+        }
+
+        // The table stores the other side to move: find the best reply
+        let mut min_dtz = i32::MAX;
+        for m in board.generate_moves().iter() {
+            let zeroing = is_zeroing(board, m);
+            let next = board.make_move_new(m);
+
+            // Zeroing moves take the DTZ of the move itself
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&next, false)?.0)
+            } else {
+                -self.probe_dtz(&next)?
+            };
+
+            if dtz == 1 && next.in_check() && next.generate_moves().is_empty() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl_sign(wdl) {
+                min_dtz = dtz;
+            }
+        }
+
+        // No legal moves: the position is mate
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    /// Rank root moves by DTZ, so that won positions get converted within
+    /// the fifty-move rule.
+    ///
+    /// `halfmove_clock` is the root's fifty-move counter and `repeated`
+    /// whether the root position occurred before since the last zeroing
+    /// move. `draws[i]` tells whether `moves[i]` draws at once by repetition
+    /// or the fifty-move rule. Returns `None` if any move can't be probed.
+    pub fn rank_root_moves(
+        &self,
+        board: &Board,
+        moves: &[Move],
+        draws: &[bool],
+        halfmove_clock: u32,
+        repeated: bool,
+        use_rule50: bool,
+    ) -> Option<Vec<RankedMove>> {
+        let cnt50 = halfmove_clock as i32;
+
+        moves.iter().zip(draws).map(|(&m, &draw)| {
+            let next = board.make_move_new(m);
+
+            let mut dtz = if is_zeroing(board, m) {
+                dtz_before_zeroing(-self.probe_wdl(&next)?)
+            } else if draw {
+                0
+            } else {
+                let dtz = -self.probe_dtz(&next)?;
+                dtz + dtz.signum()
+            };
+
+            // A mating move gets a DTZ of 1
+            if dtz == 2 && next.in_check() && next.generate_moves().is_empty() {
+                dtz = 1;
+            }
+
+            let rank = dtz_rank(dtz, cnt50, repeated);
+            Some(RankedMove { mv: m, rank, score: rank_score(rank, use_rule50) })
+        }).collect()
+    }
+
+    /// Rank root moves by WDL only, for when the DTZ tables are missing
+    pub fn rank_root_moves_wdl(&self, board: &Board, moves: &[Move], use_rule50: bool) -> Option<Vec<RankedMove>> {
+        moves.iter().map(|&m| {
+            let wdl = -self.probe_wdl(&board.make_move_new(m))?;
+
+            let rank = match wdl {
+                Wdl::Loss => -MAX_DTZ,
+                Wdl::BlessedLoss => -MAX_DTZ + 101,
+                Wdl::Draw => 0,
+                Wdl::CursedWin => MAX_DTZ - 101,
+                Wdl::Win => MAX_DTZ,
+            };
+            let score = match wdl {
+                Wdl::Win => Score::tb_win_in(MAX_PLY),
+                Wdl::CursedWin if !use_rule50 => Score::tb_win_in(MAX_PLY),
+                Wdl::CursedWin => Score::cp(2),
+                Wdl::Draw => Score::draw(),
+                Wdl::BlessedLoss if use_rule50 => Score::cp(-2),
+                Wdl::BlessedLoss | Wdl::Loss => Score::tb_loss_in(MAX_PLY),
+            };
+
+            Some(RankedMove { mv: m, rank, score })
+        }).collect()
+    }
+
+    /// Look the position up in its table
+    fn probe_table(&self, board: &Board, kind: TableKind, wdl: Wdl) -> Option<Lookup> {
+        let counts = board_counts(board);
+        if counts.iter().flatten().sum::<usize>() == 2 {
+            // Bare kings
+            return Some(Lookup::Value(0));
+        }
+
+        let key = material_key(&counts);
+        let table = self.entries.get(&key)?.table(kind)?;
+        table.probe(board, key, wdl).ok()
+    }
+
+    /// WDL probe that first resolves captures (and, with `check_zeroing`,
+    /// pawn moves), since the tables don't account for en passant and store
+    /// "don't care" values where a capture is best.
+    ///
+    /// Also returns whether the best move is zeroing.
+    fn search(&self, board: &Board, check_zeroing: bool) -> Option<(Wdl, bool)> {
+        let moves = board.generate_moves();
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+
+        for m in moves.iter() {
+            if !m.is_capture() && (!check_zeroing || !is_pawn_move(board, m)) {
+                continue;
+            }
+            searched += 1;
+
+            let value = -self.search(&board.make_move_new(m), false)?.0;
+            if value > best {
+                best = value;
+                if value >= Wdl::Win {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // With every legal move searched the table value isn't needed (and
+        // may be wrong, e.g. with en passant rights)
+        let no_more_moves = searched > 0 && searched == moves.len();
+        let value = if no_more_moves {
+            best
+        } else {
+            match self.probe_table(board, TableKind::Wdl, Wdl::Draw)? {
+                Lookup::Value(v) => Wdl::from_i32(v)?,
+                Lookup::ChangeStm => return None,
+            }
+        };
+
+        if best >= value {
+            Some((best, best > Wdl::Draw || no_more_moves))
+        } else {
+            Some((value, false))
+        }
+    }
+}
+
+/// Check if either side can still castle (tables assume no castling rights)
+pub fn has_castling_rights(board: &Board) -> bool {
+    let castling = board.castling();
+    [Color::White, Color::Black]
+        .iter()
+        .any(|&c| castling.has_kingside(c) || castling.has_queenside(c))
+}
+
+fn is_pawn_move(board: &Board, m: Move) -> bool {
+    board.piece_at(m.from()).is_some_and(|(p, _)| p == Piece::Pawn)
+}
+
+/// Check if a move resets the fifty-move counter
+pub fn is_zeroing(board: &Board, m: Move) -> bool {
+    m.is_capture() || is_pawn_move(board, m)
+}
+
+fn wdl_sign(wdl: Wdl) -> i32 {
+    (wdl as i32).signum()
+}
+
+/// DTZ of a position whose best move is zeroing
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_names() {
+        let krk = TableInfo::from_name("KRvK").unwrap();
+        assert_eq!(krk.piece_count, 3);
+        assert!(!krk.has_pawns);
+        assert!(krk.has_unique_pieces);
+        assert_ne!(krk.key, krk.key2);
+
+        let kpkp = TableInfo::from_name("KPvKP").unwrap();
+        assert_eq!(kpkp.key, kpkp.key2);
+        assert_eq!(kpkp.pawn_count, [1, 1]);
+
+        let kppvk = TableInfo::from_name("KPPvK").unwrap();
+        assert!(!kppvk.has_unique_pieces);
+        assert_eq!(kppvk.pawn_count, [2, 0]);
+
+        assert!(TableInfo::from_name("KRK").is_none());
+        assert!(TableInfo::from_name("KXvK").is_none());
+        assert!(TableInfo::from_name("KQQQQvKQQ").is_none());
+    }
+
+    #[test]
+    fn test_dtz_before_zeroing() {
+        assert_eq!(dtz_before_zeroing(Wdl::Win), 1);
+        assert_eq!(dtz_before_zeroing(-Wdl::CursedWin), -101);
+        assert_eq!(wdl_sign(Wdl::BlessedLoss), -1);
+    }
+
+    #[test]
+    fn test_rank_of_long_cursed_win() {
+        // Won but spoilt by the fifty-move rule: still ranked and scored
+        // as a (small) win, however long the conversion
+        let rank = dtz_rank(1500, 20, false);
+        assert_eq!(rank, MAX_DTZ - 1520);
+        assert_eq!(rank_score(rank, true), Score::cp(1));
+        assert_eq!(rank_score(rank, false), Score::tb_win_in(MAX_PLY));
+
+        let rank = dtz_rank(-1500, 20, false);
+        assert!(rank < 0);
+        assert_eq!(rank_score(rank, true), Score::cp(-1));
+
+        // Certain results rank equally
+        assert_eq!(dtz_rank(5, 10, false), MAX_DTZ);
+        assert_eq!(rank_score(MAX_DTZ, true), Score::tb_win_in(MAX_PLY));
+        assert_eq!(dtz_rank(-30, 10, false), -MAX_DTZ);
+        assert!(dtz_rank(5, 10, true) < MAX_DTZ);
+        assert_eq!(rank_score(dtz_rank(0, 90, false), true), Score::draw());
+    }
+}
@@ -0,0 +1,936 @@
+//! Syzygy table files: header parsing, position indexing and decompression.
+//!
+//! This is a port of the reference prober (as found in Stockfish's
+//! `tbprobe.cpp`). A table stores, per leading file and side to move, a
+//! block of Huffman/"recursive pairing" compressed values indexed by a
+//! canonical encoding of the piece placement.
+
+use crate::types::{Board, Color, Piece};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// Maximum number of pieces in a Syzygy table
+pub const TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+/// Files up to this size are read into memory, larger ones are read on demand
+const IN_MEMORY_LIMIT: u64 = 64 * 1024 * 1024;
+
+// Per-table flags
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Win/draw/loss outcome of a tablebase position, from the side to move.
+///
+/// Cursed wins and blessed losses are wins and losses that the fifty-move
+/// rule turns into draws.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    /// Convert a raw WDL table value (-2..=2)
+    pub fn from_i32(v: i32) -> Option<Self> {
+        match v {
+            -2 => Some(Wdl::Loss),
+            -1 => Some(Wdl::BlessedLoss),
+            0 => Some(Wdl::Draw),
+            1 => Some(Wdl::CursedWin),
+            2 => Some(Wdl::Win),
+            _ => None,
+        }
+    }
+}
+
+impl std::ops::Neg for Wdl {
+    type Output = Self;
+    fn neg(self) -> Self {
+        match self {
+            Wdl::Loss => Wdl::Win,
+            Wdl::BlessedLoss => Wdl::CursedWin,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin => Wdl::BlessedLoss,
+            Wdl::Win => Wdl::Loss,
+        }
+    }
+}
+
+/// Which kind of table a file holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+impl TableKind {
+    pub fn extension(self) -> &'static str {
+        match self {
+            TableKind::Wdl => "rtbw",
+            TableKind::Dtz => "rtbz",
+        }
+    }
+
+    fn magic(self) -> [u8; 4] {
+        match self {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        }
+    }
+}
+
+/// Material signature of a table, derived from its file name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableInfo {
+    /// Material key with the stronger (first named) side as white
+    pub key: u64,
+    /// Material key with the colours swapped
+    pub key2: u64,
+    pub piece_count: usize,
+    pub has_pawns: bool,
+    pub has_unique_pieces: bool,
+    /// Pawns of the leading colour, then of the other colour
+    pub pawn_count: [usize; 2],
+}
+
+/// Result of looking a position up in a single table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lookup {
+    Value(i32),
+    /// DTZ tables store one side to move only: probe the other side instead
+    ChangeStm,
+}
+
+/// Combinatorial tables used to index piece placements
+struct Encoding {
+    map_pawns: [usize; 64],
+    map_b1h1h7: [usize; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; TB_PIECES],
+    lead_pawn_idx: [[u64; 64]; TB_PIECES],
+    lead_pawns_size: [[u64; 4]; TB_PIECES],
+    /// Number of codes used by `map_kk` (462 legal king pairs)
+    kk_codes: u64,
+}
+
+#[inline]
+fn file_of(sq: usize) -> usize {
+    sq & 7
+}
+
+#[inline]
+fn rank_of(sq: usize) -> usize {
+    sq >> 3
+}
+
+/// Signed distance of a square from the a1-h8 diagonal (positive above it)
+#[inline]
+fn off_a1h8(sq: usize) -> i32 {
+    rank_of(sq) as i32 - file_of(sq) as i32
+}
+
+impl Encoding {
+    fn new() -> Self {
+        let mut e = Encoding {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; TB_PIECES],
+            lead_pawn_idx: [[0; 64]; TB_PIECES],
+            lead_pawns_size: [[0; 4]; TB_PIECES],
+            kk_codes: 0,
+        };
+
+        // Squares below the a1-h8 diagonal map to 0..27
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_a1h8(sq) < 0 {
+                e.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        // The a1-d1-d4 triangle maps to 0..9, diagonal squares last
+        let mut diagonal = Vec::new();
+        code = 0;
+        for sq in 0..28 {
+            if off_a1h8(sq) < 0 && file_of(sq) <= 3 {
+                e.map_a1d1d4[sq] = code;
+                code += 1;
+            } else if off_a1h8(sq) == 0 && file_of(sq) <= 3 {
+                diagonal.push(sq);
+            }
+        }
+        for sq in diagonal {
+            e.map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // The 462 legal placements of two kings with the first one in the
+        // a1-d1-d4 triangle; if the first is on the diagonal the second may
+        // not be above it. Placements with both on the diagonal come last.
+        let mut both_on_diagonal = Vec::new();
+        let mut kk_code = 0;
+        for idx in 0..10 {
+            for s1 in 0..28 {
+                if e.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    let touching = file_of(s1).abs_diff(file_of(s2)) <= 1
+                        && rank_of(s1).abs_diff(rank_of(s2)) <= 1;
+                    if touching || (off_a1h8(s1) == 0 && off_a1h8(s2) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        e.map_kk[idx][s2] = kk_code;
+                        kk_code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            e.map_kk[idx][s2] = kk_code;
+            kk_code += 1;
+        }
+        e.kk_codes = kk_code;
+
+        // binomial[k][n]: ways to choose k elements out of n
+        e.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..TB_PIECES.min(n + 1) {
+                e.binomial[k][n] = if k > 0 { e.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { e.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // map_pawns[] numbers a2-h7 so that the leading pawn (nearest the
+        // edge, then lowest rank) has the highest value. Leading pawn groups
+        // are indexed per file, as the tables are split by file.
+        let mut available = 47;
+        for lead in 1..TB_PIECES - 1 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let sq = rank * 8 + file;
+                    if lead == 1 {
+                        e.map_pawns[sq] = available;
+                        e.map_pawns[sq ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    e.lead_pawn_idx[lead][sq] = idx;
+                    idx += e.binomial[lead - 1][e.map_pawns[sq]];
+                }
+                e.lead_pawns_size[lead][file] = idx;
+            }
+        }
+
+        e
+    }
+}
+
+fn encoding() -> &'static Encoding {
+    static ENCODING: OnceLock<Encoding> = OnceLock::new();
+    ENCODING.get_or_init(Encoding::new)
+}
+
+/// Backing storage of a table file
+enum Source {
+    Memory(Vec<u8>),
+    File(Mutex<File>),
+}
+
+impl Source {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        if file.metadata()?.len() <= IN_MEMORY_LIMIT {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            Ok(Source::Memory(data))
+        } else {
+            Ok(Source::File(Mutex::new(file)))
+        }
+    }
+
+    /// Fill `buf` from `offset`; bytes past the end of the file read as zero
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<()> {
+        buf.fill(0);
+        match self {
+            Source::Memory(data) => {
+                let start = (offset as usize).min(data.len());
+                let end = (start + buf.len()).min(data.len());
+                buf[..end - start].copy_from_slice(&data[start..end]);
+            }
+            Source::File(file) => {
+                let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+                file.seek(SeekFrom::Start(offset))?;
+                let mut filled = 0;
+                while filled < buf.len() {
+                    match file.read(&mut buf[filled..])? {
+                        0 => break,
+                        n => filled += n,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn bytes(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; len];
+        self.read_at(offset, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Sequential little-endian reader over a table file
+struct Reader<'a> {
+    source: &'a Source,
+    pos: u64,
+}
+
+impl Reader<'_> {
+    fn bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let buf = self.source.bytes(self.pos, len)?;
+        self.pos += len as u64;
+        Ok(buf)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Skip to the next multiple of `align` bytes
+    fn align(&mut self, align: u64) {
+        self.pos = self.pos.div_ceil(align) * align;
+    }
+}
+
+#[inline]
+fn le_u16(data: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([data[2 * i], data[2 * i + 1]])
+}
+
+/// Compressed data for one (side to move, leading file) pair of a table
+#[derive(Default)]
+struct PairsData {
+    flags: u8,
+    block_size: u64,
+    span: u64,
+    num_blocks: u32,
+    block_length_size: u32,
+    sparse_index_size: u64,
+    min_sym_len: u8,
+    /// Lowest symbol of each code length, as little-endian u16s
+    lowest_sym: Vec<u8>,
+    /// Lowest symbol of each code length, left-aligned to 64 bits
+    base64: Vec<u64>,
+    /// Left and right child of each symbol, 12 bits each
+    btree: Vec<u8>,
+    /// Number of values (minus one) each symbol expands to
+    symlen: Vec<u8>,
+    /// Six-byte entries: first block (u32) and offset in it (u16)
+    sparse_index: Vec<u8>,
+    /// Number of values (minus one) stored in each block, as u16s
+    block_length: Vec<u8>,
+    data_offset: u64,
+    /// Piece codes in encoding order (1..6 white, 9..14 black)
+    pieces: [u8; TB_PIECES],
+    group_idx: [u64; TB_PIECES + 1],
+    group_len: [usize; TB_PIECES + 1],
+    /// Start of each WDL outcome's DTZ value map, relative to the map
+    map_idx: [usize; 4],
+}
+
+impl PairsData {
+    #[inline]
+    fn left(&self, sym: usize) -> usize {
+        let lr = &self.btree[3 * sym..3 * sym + 3];
+        (((lr[1] & 0xF) as usize) << 8) | lr[0] as usize
+    }
+
+    #[inline]
+    fn right(&self, sym: usize) -> usize {
+        let lr = &self.btree[3 * sym..3 * sym + 3];
+        ((lr[2] as usize) << 4) | (lr[1] >> 4) as usize
+    }
+
+    /// Split the pieces into groups that are encoded together and compute
+    /// the index multiplier of each group.
+    fn set_groups(&mut self, info: &TableInfo, order: [usize; 2], file: usize) {
+        let enc = encoding();
+        let mut n = 0;
+        let mut first_len: i32 = if info.has_pawns { 0 } else if info.has_unique_pieces { 3 } else { 2 };
+        self.group_len[0] = 1;
+
+        for i in 1..info.piece_count {
+            first_len -= 1;
+            if first_len > 0 || self.pieces[i] == self.pieces[i - 1] {
+                self.group_len[n] += 1;
+            } else {
+                n += 1;
+                self.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        self.group_len[n] = 0;
+
+        // The groups are encoded in a per-table order: the leading group is
+        // at order[0] and the remaining pawns, if any, at order[1]
+        let pp = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - self.group_len[0] - if pp { self.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        let mut k = 0;
+
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                self.group_idx[0] = idx;
+                idx *= if info.has_pawns {
+                    enc.lead_pawns_size[self.group_len[0]][file]
+                } else if info.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                self.group_idx[1] = idx;
+                idx *= enc.binomial[self.group_len[1]][48 - self.group_len[0]];
+            } else {
+                self.group_idx[next] = idx;
+                idx *= enc.binomial[self.group_len[next]][free_squares];
+                free_squares -= self.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        self.group_idx[n] = idx;
+    }
+
+    /// Read the Huffman code description and symbol tree
+    fn set_sizes(&mut self, r: &mut Reader) -> io::Result<()> {
+        self.flags = r.u8()?;
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            // Every position has the same value, stored in place of the code
+            self.min_sym_len = r.u8()?;
+            return Ok(());
+        }
+
+        let end = self.group_len.iter().position(|&len| len == 0).unwrap_or(TB_PIECES);
+        let tb_size = self.group_idx[end];
+
+        self.block_size = 1u64 << r.u8()?;
+        self.span = 1u64 << r.u8()?;
+        self.sparse_index_size = tb_size.div_ceil(self.span);
+        let padding = r.u8()?;
+        self.num_blocks = r.u32()?;
+        self.block_length_size = self.num_blocks + padding as u32;
+        let max_sym_len = r.u8()?;
+        self.min_sym_len = r.u8()?;
+        if max_sym_len < self.min_sym_len || self.min_sym_len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad symbol lengths"));
+        }
+
+        let lengths = (max_sym_len - self.min_sym_len) as usize + 1;
+        self.lowest_sym = r.bytes(2 * lengths)?;
+
+        // Longer codes have lower values, so base64[i] >= base64[i + 1]
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            self.base64[i] = self.base64[i + 1]
+                .wrapping_add(le_u16(&self.lowest_sym, i) as u64)
+                .wrapping_sub(le_u16(&self.lowest_sym, i + 1) as u64)
+                / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            let shift = 64 - i as u32 - self.min_sym_len as u32;
+            *base = base.checked_shl(shift).unwrap_or(0);
+        }
+
+        let symbols = r.u16()? as usize;
+        self.btree = r.bytes(3 * symbols)?;
+        r.pos += (symbols & 1) as u64;
+
+        // Recursive pairing: each symbol expands into a left and right pair
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(sym, &mut visited);
+            }
+        }
+        Ok(())
+    }
+
+    fn set_symlen(&mut self, sym: usize, visited: &mut [bool]) -> u8 {
+        visited[sym] = true;
+        let right = self.right(sym);
+        if right == 0xFFF {
+            return 0;
+        }
+        let left = self.left(sym);
+        for child in [left, right] {
+            if child < visited.len() && !visited[child] {
+                self.symlen[child] = self.set_symlen(child, visited);
+            }
+        }
+        let len = |s: usize| self.symlen.get(s).copied().unwrap_or(0);
+        len(left).wrapping_add(len(right)).wrapping_add(1)
+    }
+
+    /// Decompress the value stored at `idx`
+    fn decompress(&self, source: &Source, idx: u64) -> io::Result<i32> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Ok(self.min_sym_len as i32);
+        }
+        let corrupt = || io::Error::new(io::ErrorKind::InvalidData, "corrupt table");
+
+        // Locate the block holding idx from the nearest sparse index entry
+        let k = (idx / self.span) as usize;
+        let entry = self.sparse_index.get(6 * k..6 * k + 6).ok_or_else(corrupt)?;
+        let mut block = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) as usize;
+        let mut offset = u16::from_le_bytes([entry[4], entry[5]]) as i64;
+        offset += (idx % self.span) as i64 - (self.span / 2) as i64;
+
+        let block_length = |b: usize| -> io::Result<i64> {
+            if 2 * b + 1 < self.block_length.len() {
+                Ok(le_u16(&self.block_length, b) as i64)
+            } else {
+                Err(corrupt())
+            }
+        };
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or_else(corrupt)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let data = source.bytes(
+            self.data_offset + block as u64 * self.block_size,
+            self.block_size as usize + 8,
+        )?;
+        let read_u32 = |pos: usize| -> u64 {
+            data.get(pos..pos + 4)
+                .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as u64)
+        };
+
+        // Walk the canonical Huffman symbols until the one covering offset
+        let mut buf64 = (read_u32(0) << 32) | read_u32(4);
+        let mut ptr = 8;
+        let mut buf64_size = 64;
+        let min_len = self.min_sym_len as usize;
+        let mut sym;
+
+        loop {
+            let mut len = 0;
+            while len < self.base64.len() - 1 && buf64 < self.base64[len] {
+                len += 1;
+            }
+
+            sym = ((buf64 - self.base64[len]) >> (64 - len - min_len)) as usize;
+            sym += le_u16(&self.lowest_sym, len) as usize;
+            let sym_len = *self.symlen.get(sym).ok_or_else(corrupt)? as i64;
+
+            if offset < sym_len + 1 {
+                break;
+            }
+
+            offset -= sym_len + 1;
+            len += min_len;
+            buf64 = buf64.checked_shl(len as u32).unwrap_or(0);
+            buf64_size -= len as i32;
+
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= read_u32(ptr) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        // Expand the symbol down to the single value at offset
+        while self.symlen[sym] != 0 {
+            let left = self.left(sym);
+            let left_len = *self.symlen.get(left).ok_or_else(corrupt)? as i64;
+            if offset < left_len + 1 {
+                sym = left;
+            } else {
+                offset -= left_len + 1;
+                sym = self.right(sym);
+            }
+            if sym >= self.symlen.len() {
+                return Err(corrupt());
+            }
+        }
+
+        Ok(self.left(sym) as i32)
+    }
+}
+
+/// An opened WDL or DTZ table
+pub struct Table {
+    kind: TableKind,
+    info: TableInfo,
+    source: Source,
+    /// Compressed data, indexed by `[file][side]`
+    pairs: Vec<Vec<PairsData>>,
+    /// DTZ value maps (DTZ tables only)
+    map: Vec<u8>,
+}
+
+impl Table {
+    /// Open a table file and parse its header
+    pub fn open(path: &Path, kind: TableKind, info: &TableInfo) -> io::Result<Self> {
+        let source = Source::open(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        if source.bytes(0, 4)? != kind.magic() {
+            return Err(invalid("bad magic"));
+        }
+
+        let mut r = Reader { source: &source, pos: 4 };
+        let header = r.u8()?;
+        if (header & 2 != 0) != info.has_pawns || (header & 1 != 0) != (info.key != info.key2) {
+            return Err(invalid("table does not match its name"));
+        }
+
+        let sides = if kind == TableKind::Wdl && info.key != info.key2 { 2 } else { 1 };
+        let files = if info.has_pawns { 4 } else { 1 };
+        let pp = info.has_pawns && info.pawn_count[1] > 0;
+
+        let mut pairs: Vec<Vec<PairsData>> = (0..files)
+            .map(|_| (0..sides).map(|_| PairsData::default()).collect())
+            .collect();
+
+        for (file, file_pairs) in pairs.iter_mut().enumerate() {
+            let b0 = r.u8()?;
+            let b1 = if pp { r.u8()? } else { 0 };
+            let orders = [
+                [(b0 & 0xF) as usize, if pp { (b1 & 0xF) as usize } else { 0xF }],
+                [(b0 >> 4) as usize, if pp { (b1 >> 4) as usize } else { 0xF }],
+            ];
+
+            for k in 0..info.piece_count {
+                let b = r.u8()?;
+                for (side, d) in file_pairs.iter_mut().enumerate() {
+                    d.pieces[k] = if side == 0 { b & 0xF } else { b >> 4 };
+                }
+            }
+
+            for (side, d) in file_pairs.iter_mut().enumerate() {
+                d.set_groups(info, orders[side], file);
+            }
+        }
+
+        r.align(2);
+
+        for d in pairs.iter_mut().flatten() {
+            d.set_sizes(&mut r)?;
+        }
+
+        let mut map = Vec::new();
+        if kind == TableKind::Dtz {
+            let map_start = r.pos;
+            for file_pairs in pairs.iter_mut() {
+                let d = &mut file_pairs[0];
+                if d.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if d.flags & FLAG_WIDE != 0 {
+                    r.align(2);
+                    for i in 0..4 {
+                        d.map_idx[i] = (r.pos - map_start) as usize;
+                        let len = r.u16()? as u64;
+                        r.pos += 2 * len;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = (r.pos - map_start) as usize;
+                        let len = r.u8()? as u64;
+                        r.pos += len;
+                    }
+                }
+            }
+            map = source.bytes(map_start, (r.pos - map_start) as usize)?;
+            r.align(2);
+        }
+
+        for d in pairs.iter_mut().flatten() {
+            d.sparse_index = r.bytes(6 * d.sparse_index_size as usize)?;
+        }
+
+        for d in pairs.iter_mut().flatten() {
+            d.block_length = r.bytes(2 * d.block_length_size as usize)?;
+        }
+
+        for d in pairs.iter_mut().flatten() {
+            r.align(64);
+            d.data_offset = r.pos;
+            r.pos += d.num_blocks as u64 * d.block_size;
+        }
+
+        Ok(Self { kind, info: info.clone(), source, pairs, map })
+    }
+
+    /// Look up a position whose material matches this table.
+    ///
+    /// `material_key` is the position's key and `wdl` its WDL outcome, which
+    /// DTZ tables need to decode the stored value.
+    pub fn probe(&self, board: &Board, material_key: u64, wdl: Wdl) -> io::Result<Lookup> {
+        let enc = encoding();
+        let info = &self.info;
+
+        // Tables store the stronger side as white, and symmetric tables only
+        // store white to move: otherwise swap colours and mirror the ranks
+        let turn = board.turn().index();
+        let flip = (info.key == info.key2 && turn == 1) || material_key != info.key;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ turn;
+
+        let mut squares = [0usize; TB_PIECES];
+        let mut pieces = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns_cnt = 0;
+        let mut lead_pawns = 0u64;
+        let mut tb_file = 0;
+
+        // With pawns, the table is split by the file of the leading pawn
+        if info.has_pawns {
+            let color = if (self.pairs[0][0].pieces[0] ^ flip_color) >= 8 { Color::Black } else { Color::White };
+            for sq in board.piece_bb(Piece::Pawn) & board.color_bb(color) {
+                if size == TB_PIECES {
+                    break;
+                }
+                lead_pawns |= 1u64 << sq.index();
+                squares[size] = sq.index() as usize ^ flip_squares;
+                size += 1;
+            }
+            lead_pawns_cnt = size;
+
+            let lead = (0..lead_pawns_cnt)
+                .max_by_key(|&i| (enc.map_pawns[squares[i]], std::cmp::Reverse(i)))
+                .unwrap_or(0);
+            squares.swap(0, lead);
+
+            tb_file = file_of(squares[0]);
+            if tb_file > 3 {
+                tb_file = 7 - tb_file;
+            }
+        }
+
+        let side = stm.min(self.pairs[tb_file].len() - 1);
+        let d = &self.pairs[tb_file][side];
+
+        // DTZ tables are one-sided
+        let symmetric = info.key == info.key2 && !info.has_pawns;
+        if self.kind == TableKind::Dtz && (d.flags & FLAG_STM) as usize != stm && !symmetric {
+            return Ok(Lookup::ChangeStm);
+        }
+
+        for sq in board.occupied() {
+            if lead_pawns & (1u64 << sq.index()) != 0 {
+                continue;
+            }
+            if size == TB_PIECES {
+                break;
+            }
+            let Some((piece, color)) = board.piece_at(sq) else { continue };
+            squares[size] = sq.index() as usize ^ flip_squares;
+            pieces[size] = (piece.index() as u8 + 1 + 8 * color.index() as u8) ^ flip_color;
+            size += 1;
+        }
+
+        // Reorder the pieces to the sequence the table was encoded with
+        for i in lead_pawns_cnt..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|&j| pieces[j] == d.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Mirror so that the leading piece is on files a-d
+        if file_of(squares[0]) > 3 {
+            for sq in &mut squares[..size] {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+        if info.has_pawns {
+            idx = enc.lead_pawn_idx[lead_pawns_cnt][squares[0]];
+            squares[1..lead_pawns_cnt].sort_by_key(|&sq| enc.map_pawns[sq]);
+            for (i, &sq) in squares.iter().enumerate().take(lead_pawns_cnt).skip(1) {
+                idx += enc.binomial[i][enc.map_pawns[sq]];
+            }
+        } else {
+            // Mirror so that the leading piece is on ranks 1-4
+            if rank_of(squares[0]) > 3 {
+                for sq in &mut squares[..size] {
+                    *sq ^= 56;
+                }
+            }
+
+            // Mirror along a1-h8 so the first piece of the leading group off
+            // the diagonal is below it
+            for i in 0..d.group_len[0] {
+                let off = off_a1h8(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for sq in &mut squares[i..size] {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            idx = if info.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as usize;
+                let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+
+                if off_a1h8(s0) != 0 {
+                    ((enc.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2) as u64
+                } else if off_a1h8(s1) != 0 {
+                    ((6 * 63 + rank_of(s0) * 28 + enc.map_b1h1h7[s1]) * 62 + s2 - adjust2) as u64
+                } else if off_a1h8(s2) != 0 {
+                    (6 * 63 * 62 + 4 * 28 * 62
+                        + rank_of(s0) * 7 * 28
+                        + (rank_of(s1) - adjust1) * 28
+                        + enc.map_b1h1h7[s2]) as u64
+                } else {
+                    (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28
+                        + rank_of(s0) * 7 * 6
+                        + (rank_of(s1) - adjust1) * 6
+                        + (rank_of(s2) - adjust2)) as u64
+                }
+            } else {
+                enc.map_kk[enc.map_a1d1d4[squares[0]]][squares[1]]
+            };
+        }
+
+        // Encode the remaining groups, each in ascending square order and
+        // skipping the squares taken by earlier groups
+        idx *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = info.has_pawns && info.pawn_count[1] > 0;
+        let mut next = 1;
+
+        while next <= TB_PIECES && d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            if start + len > size {
+                break;
+            }
+            squares[start..start + len].sort_unstable();
+
+            let mut n = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| sq > s).count();
+                let pawn_adjust = if remaining_pawns { 8 } else { 0 };
+                n += enc.binomial[i + 1][sq - adjust - pawn_adjust];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+
+        let value = d.decompress(&self.source, idx)?;
+        Ok(Lookup::Value(match self.kind {
+            TableKind::Wdl => value - 2,
+            TableKind::Dtz => self.map_dtz(d, value, wdl),
+        }))
+    }
+
+    /// Convert a raw DTZ table value to plies
+    fn map_dtz(&self, d: &PairsData, value: i32, wdl: Wdl) -> i32 {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let mut value = value;
+
+        if d.flags & FLAG_MAPPED != 0 {
+            let start = d.map_idx[WDL_MAP[(wdl as i32 + 2) as usize]];
+            value = if d.flags & FLAG_WIDE != 0 {
+                let pos = start + 2 + 2 * value as usize;
+                self.map.get(pos..pos + 2).map_or(0, |b| u16::from_le_bytes([b[0], b[1]]) as i32)
+            } else {
+                self.map.get(start + 1 + value as usize).map_or(0, |&b| b as i32)
+            };
+        }
+
+        // Tables store moves rather than plies unless flagged otherwise
+        if (wdl == Wdl::Win && d.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && d.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+
+        value + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_tables() {
+        let enc = encoding();
+
+        // 462 legal king pairs with the first king in the a1-d1-d4 triangle
+        assert_eq!(enc.kk_codes, 462);
+        assert_eq!(enc.map_b1h1h7.iter().max(), Some(&27));
+        assert_eq!(enc.map_a1d1d4.iter().max(), Some(&9));
+
+        assert_eq!(enc.binomial[2][62], 62 * 61 / 2);
+        assert_eq!(enc.binomial[0][10], 1);
+
+        // a2 is the most leading pawn square, d7/e7 the least
+        assert_eq!(enc.map_pawns[8], 47);
+        assert_eq!(enc.map_pawns[15], 46);
+        assert_eq!(enc.map_pawns[51], 1);
+        assert_eq!(enc.map_pawns[52], 0);
+    }
+
+    #[test]
+    fn test_wdl_negation() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert_eq!(Wdl::from_i32(1), Some(Wdl::CursedWin));
+        assert_eq!(Wdl::from_i32(3), None);
+    }
+}
@@ -0,0 +1,14 @@
+//! Developer tools built into the engine binary.
+//!
+//! - `bench`: fixed-depth search over a built-in position suite, used as a
+//!   node-count signature and speed test
+//! - `perft`: move tree leaf counts, for validating move generation
+//! - `testsuite`: solve rate of EPD tactical suites at a fixed move time
+
+mod bench;
+mod perft;
+mod testsuite;
+
+pub use bench::{run_bench, BenchResult, BENCH_FENS, DEFAULT_BENCH_DEPTH};
+pub use perft::{perft, divide, parse_perft_epd};
+pub use testsuite::{run_testsuite, EpdRecord, PositionResult, SuiteSummary};
@@ -0,0 +1,93 @@
+//! Perft: count the leaf nodes of the legal move tree to a fixed depth.
+//!
+//! The counts for well-known positions are published, so any mismatch
+//! points at a move generation bug. `divide` splits the count by root move
+//! to narrow a mismatch down to a single line.
+
+use crate::types::{Board, Move};
+
+/// Count the leaf nodes `depth` plies below `board`
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = board.generate_moves();
+
+    // Moves are legal, so the last ply doesn't need to be played
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    moves.iter()
+        .map(|m| perft(&board.make_move_new(m), depth - 1))
+        .sum()
+}
+
+/// Perft split by root move, in move generation order
+pub fn divide(board: &Board, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+
+    board.generate_moves().iter()
+        .map(|m| (m, perft(&board.make_move_new(m), depth - 1)))
+        .collect()
+}
+
+/// Parse a perft EPD line of the form `<fen> ;D1 20 ;D2 400 ...` into the
+/// FEN and its `(depth, nodes)` entries
+pub fn parse_perft_epd(line: &str) -> Option<(&str, Vec<(u32, u64)>)> {
+    let mut fields = line.split(';');
+    let fen = fields.next()?.trim();
+    if fen.is_empty() {
+        return None;
+    }
+
+    let counts = fields
+        .filter_map(|field| {
+            let (depth, nodes) = field.trim().split_once(' ')?;
+            Some((depth.strip_prefix('D')?.parse().ok()?, nodes.trim().parse().ok()?))
+        })
+        .collect();
+
+    Some((fen, counts))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Published perft counts: the standard positions plus positions aimed at
+    /// castling through check, en passant pins, promotions and stalemate
+    const PERFT_SUITE: &str = include_str!("perft.epd");
+
+    #[test]
+    fn test_parse_perft_epd() {
+        let (fen, counts) = parse_perft_epd("8/8/8/8/8/8/8/K1k5 w - - 0 1 ;D1 3 ;D2 15").unwrap();
+        assert_eq!(fen, "8/8/8/8/8/8/8/K1k5 w - - 0 1");
+        assert_eq!(counts, vec![(1, 3), (2, 15)]);
+        assert!(parse_perft_epd("").is_none());
+    }
+
+    #[test]
+    fn test_perft_suite() {
+        for line in PERFT_SUITE.lines() {
+            let Some((fen, counts)) = parse_perft_epd(line) else {
+                continue;
+            };
+            let board = Board::from_fen(fen).expect("valid FEN");
+            for (depth, expected) in counts {
+                assert_eq!(perft(&board, depth), expected, "{} depth {}", fen, depth);
+            }
+        }
+    }
+
+    #[test]
+    fn test_divide_sums_to_perft() {
+        let board = Board::startpos();
+        let split = divide(&board, 3);
+        assert_eq!(split.len(), 20);
+        assert_eq!(split.iter().map(|&(_, n)| n).sum::<u64>(), perft(&board, 3));
+    }
+}
@@ -0,0 +1,210 @@
+//! EPD test-suite runner (WAC, STS, ECM style suites).
+//!
+//! Each EPD record is a position (the first four FEN fields) followed by
+//! `;`-terminated operations. The runner uses `bm` (best moves, any of
+//! which solves the position), `am` (moves to avoid) and `id`; moves may be
+//! written in SAN or UCI notation.
+
+use crate::eval::nnue;
+use crate::search::{Searcher, SearchLimits};
+use crate::types::{Board, Move};
+use crate::uci::{fen_halfmove_clock, parse_move, parse_san};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// One position of a test suite
+#[derive(Debug, Clone, Default)]
+pub struct EpdRecord {
+    pub fen: String,
+    pub id: Option<String>,
+    /// `bm` operands, as written in the file
+    pub best_moves: Vec<String>,
+    /// `am` operands, as written in the file
+    pub avoid_moves: Vec<String>,
+}
+
+impl EpdRecord {
+    /// Parse one EPD line. Returns `None` for blank lines, comments and
+    /// lines without a position.
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        // Position: placement, side to move, castling, en passant
+        let mut rest = line;
+        let mut position = Vec::with_capacity(4);
+        for _ in 0..4 {
+            let s = rest.trim_start();
+            let end = s.find(char::is_whitespace).unwrap_or(s.len());
+            if end == 0 {
+                return None;
+            }
+            position.push(&s[..end]);
+            rest = &s[end..];
+        }
+
+        let mut record = Self::default();
+        let mut halfmove = "0".to_string();
+        let mut fullmove = "1".to_string();
+
+        for op in rest.split(';') {
+            let op = op.trim();
+            let (opcode, operands) = op.split_once(char::is_whitespace).unwrap_or((op, ""));
+            let operands = operands.trim();
+            match opcode {
+                "bm" => record.best_moves = operands.split_whitespace().map(str::to_string).collect(),
+                "am" => record.avoid_moves = operands.split_whitespace().map(str::to_string).collect(),
+                "id" => record.id = Some(operands.trim_matches('"').to_string()),
+                "hmvc" => halfmove = operands.to_string(),
+                "fmvn" => fullmove = operands.to_string(),
+                _ => {}
+            }
+        }
+
+        record.fen = format!("{} {} {}", position.join(" "), halfmove, fullmove);
+        Some(record)
+    }
+}
+
+/// Outcome of searching one suite position
+#[derive(Debug, Clone)]
+pub struct PositionResult {
+    /// 1-based index of the position in the suite
+    pub index: usize,
+    pub id: Option<String>,
+    /// Best move found, in UCI notation
+    pub found: Option<String>,
+    pub solved: bool,
+    /// When the solving move was settled on (ms), if solved
+    pub time_to_solve_ms: Option<u64>,
+    /// Set when the FEN or an expected move couldn't be parsed
+    pub error: Option<String>,
+}
+
+/// Totals of a suite run
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SuiteSummary {
+    pub positions: usize,
+    pub solved: usize,
+    pub errors: usize,
+    /// Sum of time-to-solution over solved positions (ms)
+    pub solve_time_ms: u64,
+    pub nodes: u64,
+}
+
+/// Resolve SAN or UCI move strings against a board
+fn resolve_moves(board: &Board, moves: &[String]) -> Result<Vec<Move>, String> {
+    moves.iter()
+        .map(|s| parse_san(board, s).or_else(|| parse_move(board, s)).ok_or_else(|| format!("bad move {}", s)))
+        .collect()
+}
+
+/// Search one record and judge the result
+fn run_position(searcher: &mut Searcher, index: usize, record: &EpdRecord, movetime: u64) -> (PositionResult, u64) {
+    let mut result = PositionResult {
+        index,
+        id: record.id.clone(),
+        found: None,
+        solved: false,
+        time_to_solve_ms: None,
+        error: None,
+    };
+
+    let board = match Board::from_fen(&record.fen) {
+        Ok(b) => b,
+        Err(e) => {
+            result.error = Some(format!("invalid FEN: {}", e));
+            return (result, 0);
+        }
+    };
+    let (best, avoid) = match (resolve_moves(&board, &record.best_moves), resolve_moves(&board, &record.avoid_moves)) {
+        (Ok(best), Ok(avoid)) if !best.is_empty() || !avoid.is_empty() => (best, avoid),
+        (Err(e), _) | (_, Err(e)) => {
+            result.error = Some(e);
+            return (result, 0);
+        }
+        _ => {
+            result.error = Some("no bm or am operation".to_string());
+            return (result, 0);
+        }
+    };
+
+    // Positions are independent: don't let one warm the TT for the next
+    searcher.new_game();
+    searcher.set_position(board);
+    searcher.set_halfmove_clock(fen_halfmove_clock(&record.fen));
+    searcher.search(SearchLimits::movetime(movetime).with_move_overhead(0));
+
+    let stats = searcher.stats();
+    if let Some(m) = searcher.best_move() {
+        result.found = Some(m.to_uci());
+        result.solved = (best.is_empty() || best.contains(&m)) && !avoid.contains(&m);
+        if result.solved {
+            result.time_to_solve_ms = Some(stats.best_move_time_ms);
+        }
+    }
+
+    (result, stats.nodes)
+}
+
+/// Run every position of an EPD file for `movetime` ms each, calling
+/// `report` after each position.
+pub fn run_testsuite(
+    path: &Path,
+    movetime: u64,
+    threads: usize,
+    hash_mb: usize,
+    model: Option<nnue::Model>,
+    mut report: impl FnMut(&PositionResult),
+) -> io::Result<SuiteSummary> {
+    let contents = fs::read_to_string(path)?;
+
+    let mut searcher = Searcher::with_hash_size(hash_mb);
+    searcher.set_threads(threads);
+    searcher.set_nnue(model);
+
+    let mut summary = SuiteSummary::default();
+    for (index, record) in contents.lines().filter_map(EpdRecord::parse).enumerate() {
+        let (result, nodes) = run_position(&mut searcher, index + 1, &record, movetime);
+
+        summary.positions += 1;
+        summary.nodes += nodes;
+        if result.error.is_some() {
+            summary.errors += 1;
+        }
+        if let Some(ms) = result.time_to_solve_ms {
+            summary.solved += 1;
+            summary.solve_time_ms += ms;
+        }
+        report(&result);
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_epd_record() {
+        let record = EpdRecord::parse(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+        )
+        .unwrap();
+        assert_eq!(record.fen, "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+        assert_eq!(record.best_moves, vec!["Qg6"]);
+        assert!(record.avoid_moves.is_empty());
+        assert_eq!(record.id.as_deref(), Some("WAC.001"));
+
+        let record = EpdRecord::parse("8/8/8/8/8/8/8/K1k5 b - - am Kb1 Kd1; hmvc 12; fmvn 40;").unwrap();
+        assert_eq!(record.fen, "8/8/8/8/8/8/8/K1k5 b - - 12 40");
+        assert_eq!(record.avoid_moves, vec!["Kb1", "Kd1"]);
+
+        assert!(EpdRecord::parse("").is_none());
+        assert!(EpdRecord::parse("# comment").is_none());
+    }
+}
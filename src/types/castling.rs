@@ -0,0 +1,199 @@
+//! Castling helpers for both standard chess and Chess960.
+//!
+//! Castling moves are recognised by their `KingCastle`/`QueenCastle` flag.
+//! The castling rook is looked up on the king's rank instead of being
+//! assumed on the a/h file, so the same code serves Chess960 positions.
+//! Which castling moves are legal is up to `movegen`, except that its
+//! four castling flags don't say which rook holds a right; `CastlingRooks`
+//! keeps that from the FEN.
+
+use movegen::File;
+use super::{Board, Color, Move, MoveFlag, Piece, Square};
+
+/// Check if a move is castling
+#[inline]
+pub fn is_castling(m: Move) -> bool {
+    matches!(m.flag(), MoveFlag::KingCastle | MoveFlag::QueenCastle)
+}
+
+/// Find the rook a castling move castles with.
+///
+/// Every square between the king and its castling rook must be empty, so
+/// this is the nearest own rook on the king's rank in the castling direction.
+pub fn castling_rook_square(board: &Board, m: Move) -> Option<Square> {
+    let from = m.from();
+    let (_, color) = board.piece_at(from)?;
+    let king_file = from.file().index();
+
+    let files: Vec<u8> = match m.flag() {
+        MoveFlag::KingCastle => (king_file + 1..8).collect(),
+        MoveFlag::QueenCastle => (0..king_file).rev().collect(),
+        _ => return None,
+    };
+
+    files.into_iter()
+        .map(|f| Square::from_file_rank(File::from_index(f).unwrap(), from.rank()))
+        .find(|&sq| board.piece_at(sq) == Some((Piece::Rook, color)))
+}
+
+/// Files of the rooks that hold the castling rights.
+///
+/// With two rooks on one side of the king (common in Chess960), `movegen`
+/// can't tell which of them may castle. The files come from the FEN the
+/// game was set up from and stay valid for the whole game, since a right
+/// is lost as soon as its rook moves. `None` lets the nearest rook castle,
+/// which is all standard chess needs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CastlingRooks {
+    /// By colour (white first), then kingside and queenside
+    files: [[Option<u8>; 2]; 2],
+}
+
+impl CastlingRooks {
+    /// Read the castling field of a standard FEN, Shredder-FEN (`HAha`)
+    /// or X-FEN. `K`/`Q` name the outermost rook on that side of the king,
+    /// a file letter the rook on that file.
+    pub fn from_fen(fen: &str) -> Self {
+        let mut rooks = Self::default();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let (Some(placement), Some(castling)) = (fields.first(), fields.get(2)) else {
+            return rooks;
+        };
+
+        // Pieces of a back rank by file
+        let ranks: Vec<&str> = placement.split('/').collect();
+        let back_rank = |rank: Option<&&str>| -> [Option<char>; 8] {
+            let mut squares = [None; 8];
+            let mut file = 0usize;
+            for c in rank.map_or("", |r| *r).chars() {
+                match c.to_digit(10) {
+                    Some(n) => file += n as usize,
+                    None => {
+                        if file < 8 {
+                            squares[file] = Some(c);
+                        }
+                        file += 1;
+                    }
+                }
+            }
+            squares
+        };
+        let back_ranks = [back_rank(ranks.last()), back_rank(ranks.first())];
+
+        for c in castling.chars() {
+            let (index, king, rook) = if c.is_ascii_uppercase() { (0, 'K', 'R') } else { (1, 'k', 'r') };
+            let squares = &back_ranks[index];
+            let Some(king_file) = squares.iter().position(|&p| p == Some(king)) else { continue };
+            let rook_files = (0..8u8).filter(|&f| squares[f as usize] == Some(rook));
+
+            let (side, file) = match c.to_ascii_lowercase() {
+                'k' => (0, rook_files.filter(|&f| f as usize > king_file).max()),
+                'q' => (1, rook_files.filter(|&f| (f as usize) < king_file).min()),
+                f @ 'a'..='h' => {
+                    let file = f as u8 - b'a';
+                    (if file as usize > king_file { 0 } else { 1 }, Some(file))
+                }
+                _ => continue,
+            };
+            rooks.files[index][side] = file;
+        }
+        rooks
+    }
+
+    /// Check that a castling move castles with the rook holding the right
+    /// (every other move passes)
+    pub fn allows(&self, board: &Board, m: Move) -> bool {
+        if !is_castling(m) {
+            return true;
+        }
+        let Some((_, color)) = board.piece_at(m.from()) else {
+            return true;
+        };
+        let index = if color == Color::White { 0 } else { 1 };
+        let side = if m.flag() == MoveFlag::KingCastle { 0 } else { 1 };
+        match self.files[index][side] {
+            Some(file) => castling_rook_square(board, m).is_some_and(|sq| sq.file().index() == file),
+            None => true,
+        }
+    }
+}
+
+/// Final king and rook squares of a castling move: g and f files when
+/// castling kingside, c and d files when castling queenside.
+pub fn castling_destinations(m: Move) -> (Square, Square) {
+    let rank = m.from().rank();
+    if m.flag() == MoveFlag::KingCastle {
+        (Square::from_file_rank(File::G, rank), Square::from_file_rank(File::F, rank))
+    } else {
+        (Square::from_file_rank(File::C, rank), Square::from_file_rank(File::D, rank))
+    }
+}
+
+/// Find the legal castling move written as "king takes own rook"
+/// (Chess960 UCI notation and Polyglot books).
+pub fn find_castling_move(board: &Board, king: Square, rook: Square) -> Option<Move> {
+    let (Some((Piece::King, color)), Some((Piece::Rook, rook_color))) =
+        (board.piece_at(king), board.piece_at(rook))
+    else {
+        return None;
+    };
+    if color != rook_color || king.rank() != rook.rank() {
+        return None;
+    }
+
+    let flag = if rook.file().index() > king.file().index() {
+        MoveFlag::KingCastle
+    } else {
+        MoveFlag::QueenCastle
+    };
+    board.generate_moves().iter()
+        .find(|m| m.from() == king && m.flag() == flag && castling_rook_square(board, *m) == Some(rook))
+}
+
+/// Algebraic name of a square (e.g. "e1")
+pub fn square_name(sq: Square) -> String {
+    format!("{}{}", (b'a' + sq.file().index()) as char, (b'1' + sq.rank().index()) as char)
+}
+
+/// Format a move in UCI notation. In Chess960 mode castling is written as
+/// the king taking its own rook (e.g. "e1h1" instead of "e1g1").
+pub fn move_to_uci(board: &Board, m: Move, chess960: bool) -> String {
+    if chess960 && is_castling(m) {
+        if let Some(rook) = castling_rook_square(board, m) {
+            return format!("{}{}", square_name(m.from()), square_name(rook));
+        }
+    }
+    m.to_uci()
+}
+
+/// Format a line of moves played from `board` in UCI notation
+pub fn line_to_uci(board: &Board, moves: &[Move], chess960: bool) -> String {
+    let mut board = *board;
+    let mut out = Vec::with_capacity(moves.len());
+    for &m in moves {
+        out.push(move_to_uci(&board, m, chess960));
+        board = board.make_move_new(m);
+    }
+    out.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_castling_rooks_from_fen() {
+        // Shredder-FEN and X-FEN name the same rooks
+        let shredder = CastlingRooks::from_fen("rk4r1/8/8/8/8/8/8/RK4R1 w GAga - 0 1");
+        let xfen = CastlingRooks::from_fen("rk4r1/8/8/8/8/8/8/RK4R1 w KQkq - 0 1");
+        assert_eq!(shredder, xfen);
+        assert_eq!(shredder.files, [[Some(6), Some(0)], [Some(6), Some(0)]]);
+
+        // Two rooks on the queenside: `Q` is the outer one, `B` the inner one
+        let outer = CastlingRooks::from_fen("4k3/8/8/8/8/8/8/RR2K3 w Q - 0 1");
+        let inner = CastlingRooks::from_fen("4k3/8/8/8/8/8/8/RR2K3 w B - 0 1");
+        assert_eq!(outer.files[0], [None, Some(0)]);
+        assert_eq!(inner.files[0], [None, Some(1)]);
+        assert_eq!(CastlingRooks::from_fen("4k3/8/8/8/8/8/8/RR2K3 w - - 0 1"), CastlingRooks::default());
+    }
+}
@@ -12,11 +12,14 @@
 mod score;
 mod depth;
 mod convert;
+mod prng;
+pub mod castling;
 
 // Re-export our custom types
-pub use score::{Score, SCORE_INFINITY, SCORE_MATE, SCORE_DRAW, SCORE_NONE};
+pub use score::{Score, SCORE_INFINITY, SCORE_MATE, SCORE_DRAW, SCORE_NONE, SCORE_TB_WIN};
 pub use depth::{Depth, Ply, MAX_DEPTH, MAX_PLY};
 pub use convert::{ToNnue, nnue_color_flip};
+pub use prng::Prng;
 
 // Re-export movegen crate types as canonical types
 // This gives us a single source of truth and avoids confusion
@@ -3,6 +3,7 @@
 //! Handles regular centipawn scores, mate scores, draws, and special values.
 //! Optimized for alpha-beta search with proper mate score handling.
 
+use super::depth::MAX_PLY;
 use std::fmt;
 use std::ops::{Add, Sub, Neg};
 
@@ -16,6 +17,10 @@ pub const SCORE_DRAW: i32 = 0;
 const SCORE_MATE_IN_MAX: i32 = SCORE_MATE - 1000;
 const SCORE_MATED_IN_MAX: i32 = -SCORE_MATE + 1000;
 
+/// Tablebase win at the root; wins found deeper score slightly lower
+pub const SCORE_TB_WIN: i32 = SCORE_MATE_IN_MAX - 1;
+const SCORE_TB_WIN_IN_MAX: i32 = SCORE_TB_WIN - MAX_PLY;
+
 /// A chess engine score.
 ///
 /// Internally stored as centipawns with special encoding for mate scores.
@@ -43,6 +48,18 @@ impl Score {
         Score(-SCORE_MATE + ply)
     }
 
+    /// Create a tablebase win score (win proven N plies from root)
+    #[inline]
+    pub const fn tb_win_in(ply: i32) -> Self {
+        Score(SCORE_TB_WIN - ply)
+    }
+
+    /// Create a tablebase loss score (loss proven N plies from root)
+    #[inline]
+    pub const fn tb_loss_in(ply: i32) -> Self {
+        Score(-SCORE_TB_WIN + ply)
+    }
+
     /// Draw score
     #[inline]
     pub const fn draw() -> Self {
@@ -91,6 +108,12 @@ impl Score {
         self.is_mate() || self.is_mated()
     }
 
+    /// Check if this is a tablebase win or loss (not a mate)
+    #[inline]
+    pub const fn is_tb_score(self) -> bool {
+        !self.is_mate_score() && self.0.abs() >= SCORE_TB_WIN_IN_MAX
+    }
+
     /// Get mate distance in plies (if this is a mate score)
     #[inline]
     pub const fn mate_distance(self) -> Option<i32> {
@@ -103,24 +126,24 @@ impl Score {
         }
     }
 
-    /// Adjust a mate score when storing in TT (relative to current ply)
+    /// Adjust a mate or tablebase score when storing in TT (relative to current ply)
     #[inline]
     pub const fn to_tt(self, ply: i32) -> Self {
-        if self.is_mate() {
+        if self.0 >= SCORE_TB_WIN_IN_MAX {
             Score(self.0 + ply)
-        } else if self.is_mated() {
+        } else if self.0 <= -SCORE_TB_WIN_IN_MAX {
             Score(self.0 - ply)
         } else {
             self
         }
     }
 
-    /// Adjust a mate score when retrieving from TT
+    /// Adjust a mate or tablebase score when retrieving from TT
     #[inline]
     pub const fn from_tt(self, ply: i32) -> Self {
-        if self.is_mate() {
+        if self.0 >= SCORE_TB_WIN_IN_MAX {
             Score(self.0 - ply)
-        } else if self.is_mated() {
+        } else if self.0 <= -SCORE_TB_WIN_IN_MAX {
             Score(self.0 + ply)
         } else {
             self
@@ -165,8 +188,9 @@ impl fmt::Display for Score {
             let moves = (SCORE_MATE - self.0 + 1) / 2;
             write!(f, "mate {}", moves)
         } else if self.is_mated() {
+            // Signed so that being mated already prints "mate 0", not "mate -0"
             let moves = (self.0 + SCORE_MATE + 1) / 2;
-            write!(f, "mate -{}", moves)
+            write!(f, "mate {}", -moves)
         } else {
             write!(f, "cp {}", self.0)
         }
@@ -202,5 +226,20 @@ mod tests {
         let tt_score = mate.to_tt(2);
         let restored = tt_score.from_tt(2);
         assert_eq!(mate, restored);
+
+        let tb_win = Score::tb_win_in(7);
+        assert!(tb_win.is_tb_score());
+        assert!(!tb_win.is_mate_score());
+        assert_eq!(tb_win.to_tt(7), Score::tb_win_in(0));
+        assert_eq!(Score::tb_loss_in(7).to_tt(7).from_tt(3), Score::tb_loss_in(3));
+    }
+
+    #[test]
+    fn test_uci_display() {
+        assert_eq!(Score::cp(-35).to_string(), "cp -35");
+        assert_eq!(Score::mate_in(1).to_string(), "mate 1");
+        assert_eq!(Score::mate_in(5).to_string(), "mate 3");
+        assert_eq!(Score::mated_in(4).to_string(), "mate -2");
+        assert_eq!(Score::mated_in(0).to_string(), "mate 0");
     }
 }
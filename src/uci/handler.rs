@@ -1,31 +1,104 @@
 //! UCI command handler and main loop.
 
 use super::parser::{parse_command, UciCommand};
-use super::{parse_move, format_move, SearchParams, ENGINE_NAME, ENGINE_AUTHOR};
-use crate::types::{Board, Move, Score};
-use crate::search::{Searcher, SearchLimits};
+use super::{line_to_san, parse_move, format_move_960, standard_castling_fen, fen_halfmove_clock, fen_game_ply, SearchParams, ENGINE_NAME, ENGINE_AUTHOR};
+use crate::types::{Board, Color, Move, Prng, Score};
+use crate::types::castling::{self, CastlingRooks};
+use crate::search::{is_insufficient_material, Searcher, SearchLimits, SharedState, Skill, TimeManager};
+use crate::search::{SearchListener, SilentListener, SearchStats, PvInfo, CurrMoveInfo, ProgressInfo};
+use crate::search::tt::BoundType;
 use crate::eval::nnue;
-use crate::book::PolyglotBook;
+use crate::book::{self, BookBuilder, Outcome, PolyglotBook};
+use crate::pgn::GameResult;
+use crate::syzygy::{is_zeroing, Tablebases};
+use crate::tools;
 use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Default `BookDepth`, in full moves (also its maximum)
+const DEFAULT_BOOK_DEPTH: u32 = 255;
+/// Default `BookCheckMargin`, in centipawns
+const DEFAULT_BOOK_CHECK_MARGIN: i32 = 100;
+/// Node budget of the search that vets one book move (`BookCheckDepth`)
+const BOOK_CHECK_NODES: u64 = 200_000;
+
+/// Book moves the engine played this game, for book learning
+#[derive(Debug, Default)]
+struct BookLine {
+    /// (Polyglot key, Polyglot move) of each book move
+    moves: Vec<(u64, u16)>,
+    /// Side the engine played them for
+    side: Option<Color>,
+}
+
+impl BookLine {
+    fn record(&mut self, board: &Board, m: Move) {
+        self.moves.push((book::polyglot_hash(board), book::encode_move(board, m)));
+        self.side = Some(board.turn());
+    }
+}
 
 /// UCI protocol handler
 pub struct UciHandler {
     /// Current board position
     board: Board,
-    /// Search engine
-    searcher: Searcher,
+    /// Fifty-move counter of the current position, in plies
+    halfmove_clock: u32,
+    /// Plies played in the game before the current position
+    game_ply: u32,
+    /// Search engine (locked by the search thread while a search runs)
+    searcher: Arc<Mutex<Searcher>>,
+    /// Shared search state, used to signal `stop` to a running search
+    shared: Arc<SharedState>,
+    /// Background thread running the current search, if any
+    search_thread: Option<JoinHandle<()>>,
     /// Opening book
     book: Option<PolyglotBook>,
     /// Use opening book
     use_own_book: bool,
     /// Path to opening book file
     book_path: String,
+    /// `BookDepth` option: last full move to play from the book
+    book_depth: u32,
+    /// `BookBestMove` option: always play the heaviest book move
+    book_best_move: bool,
+    /// `BookMinWeight` option: ignore lighter book moves
+    book_min_weight: u16,
+    /// `BookCheckDepth` option: depth of the search that vets book moves
+    /// (0 = play them unchecked)
+    book_check_depth: i32,
+    /// `BookCheckMargin` option: skip book moves that search below minus
+    /// this many centipawns
+    book_check_margin: i32,
+    /// Picks weighted book moves (`BookSeed` option, 0 = seeded from the clock)
+    book_rng: Prng,
+    /// `BookLearning` option: reweight the book by the results of games
+    book_learning: bool,
+    /// Book moves played this game (also recorded by the search thread
+    /// when it plays a vetted book move)
+    book_line: Arc<Mutex<BookLine>>,
+    /// `BookLearningGuess` option: without a known result, take the last
+    /// search score as the game's outcome
+    book_learning_guess: bool,
+    /// Result of the game if the last `position` ended it (mate or a draw
+    /// rule)
+    game_result: Option<GameResult>,
+    /// Whether the engine has searched a position this game
+    searched_this_game: bool,
     /// Debug mode enabled
     debug: bool,
     /// Should the engine quit
     quit: bool,
     /// Move overhead in milliseconds (safety buffer for time control)
     move_overhead: u64,
+    /// Chess960 mode: castling is written as king takes rook
+    chess960: bool,
+    /// Writes the search output
+    listener: Arc<UciListener>,
 }
 
 impl Default for UciHandler {
@@ -97,19 +170,43 @@ impl UciHandler {
             }
         };
 
+        let listener = Arc::new(UciListener::new());
+        searcher.set_listener(listener.clone());
+        let shared = Arc::clone(&searcher.shared);
+
         Self {
             board: Board::default(),
-            searcher,
+            halfmove_clock: 0,
+            game_ply: 0,
+            searcher: Arc::new(Mutex::new(searcher)),
+            shared,
+            search_thread: None,
             book,
             use_own_book: true, // Enable book by default
             book_path,
+            book_depth: DEFAULT_BOOK_DEPTH,
+            book_best_move: false,
+            book_min_weight: 1,
+            book_check_depth: 0,
+            book_check_margin: DEFAULT_BOOK_CHECK_MARGIN,
+            book_rng: Prng::from_time(),
+            book_learning: false,
+            book_line: Arc::new(Mutex::new(BookLine::default())),
+            book_learning_guess: false,
+            game_result: None,
+            searched_this_game: false,
             debug: false,
             quit: false,
             move_overhead: 10, // Default 10ms
+            chess960: false,
+            listener,
         }
     }
 
     /// Run the UCI main loop (blocking)
+    ///
+    /// Searches run on a background thread, so this loop keeps reading
+    /// stdin and can act on `stop`, `isready` and `quit` mid-search.
     pub fn run(&mut self) {
         let stdin = io::stdin();
         let reader = stdin.lock();
@@ -128,6 +225,9 @@ impl UciHandler {
                 Err(_) => break,
             }
         }
+
+        // Don't leave a search running after stdin closes
+        self.stop_search();
     }
 
     /// Handle a single UCI command
@@ -145,11 +245,18 @@ impl UciHandler {
             UciCommand::Register => {} // Ignore registration
             UciCommand::UciNewGame => self.cmd_ucinewgame(),
             UciCommand::Position { fen, moves } => self.cmd_position(fen.as_deref(), &moves),
-            UciCommand::Go(params) => self.cmd_go(params),
+            UciCommand::Go { params, searchmoves } => self.cmd_go(params, &searchmoves),
             UciCommand::Stop => self.cmd_stop(),
             UciCommand::PonderHit => self.cmd_ponderhit(),
             UciCommand::Quit => self.cmd_quit(),
             UciCommand::Display => self.cmd_display(),
+            UciCommand::Bench { depth, threads, hash } => self.cmd_bench(depth, threads, hash),
+            UciCommand::Perft(depth) => self.cmd_perft(depth),
+            UciCommand::TestSuite { path, movetime } => self.cmd_testsuite(&path, movetime),
+            UciCommand::MakeBook { pgn, book, max_ply, min_games } => {
+                self.cmd_makebook(&pgn, &book, max_ply, min_games)
+            }
+            UciCommand::Result(result) => self.finish_book_learning(Some(result)),
             UciCommand::Unknown(s) => {
                 if self.debug {
                     eprintln!("Unknown command: {}", s);
@@ -160,8 +267,30 @@ impl UciHandler {
 
     /// Send output to GUI
     fn send(&self, msg: &str) {
-        println!("{}", msg);
-        io::stdout().flush().ok();
+        send(msg);
+    }
+
+    /// Block until the running search (if any) has finished on its own
+    fn wait_for_search(&mut self) {
+        if let Some(handle) = self.search_thread.take() {
+            let _ = handle.join();
+        }
+    }
+
+    /// Stop the running search (if any) and wait for its `bestmove`
+    fn stop_search(&mut self) {
+        if self.search_thread.is_some() {
+            self.shared.stop.store(true, Ordering::Relaxed);
+            self.wait_for_search();
+        }
+    }
+
+    /// Lock the searcher, stopping the running search (if any) first: a
+    /// command that changes the position or options ends `go infinite` and
+    /// `go ponder` instead of waiting for them forever
+    fn searcher(&mut self) -> MutexGuard<'_, Searcher> {
+        self.stop_search();
+        self.searcher.lock().unwrap_or_else(|e| e.into_inner())
     }
 
     // === UCI Commands ===
@@ -171,16 +300,37 @@ impl UciHandler {
         self.send(&format!("id author {}", ENGINE_AUTHOR));
         
         // Send options
+        self.send(&format!("option name Hash type spin default 16 min 1 max {}", Searcher::MAX_HASH_MB));
+        self.send("option name Clear Hash type button");
         self.send("option name Threads type spin default 1 min 1 max 64");
+        self.send("option name MultiPV type spin default 1 min 1 max 256");
         self.send("option name MoveOverhead type spin default 10 min 0 max 5000");
+        self.send("option name Ponder type check default false");
         self.send("option name OwnBook type check default true");
         self.send("option name BookPath type string default Openings.bin");
+        self.send(&format!("option name BookDepth type spin default {} min 0 max {}", DEFAULT_BOOK_DEPTH, DEFAULT_BOOK_DEPTH));
+        self.send("option name BookBestMove type check default false");
+        self.send("option name BookMinWeight type spin default 1 min 1 max 65535");
+        self.send("option name BookSeed type spin default 0 min 0 max 2147483647");
+        self.send("option name BookCheckDepth type spin default 0 min 0 max 20");
+        self.send(&format!("option name BookCheckMargin type spin default {} min 0 max 1000", DEFAULT_BOOK_CHECK_MARGIN));
+        self.send("option name BookLearning type check default false");
+        self.send("option name BookLearningGuess type check default false");
+        self.send("option name UCI_Chess960 type check default false");
+        self.send("option name ShowSanPv type check default false");
+        self.send("option name SyzygyPath type string default <empty>");
+        self.send("option name SyzygyProbeDepth type spin default 1 min 1 max 100");
+        self.send("option name Syzygy50MoveRule type check default true");
+        self.send(&format!("option name Contempt type spin default {} min -100 max 100", Searcher::DEFAULT_CONTEMPT));
+        self.send("option name AnalysisContempt type check default false");
+        self.send(&format!("option name Skill Level type spin default {} min 0 max {}", Skill::MAX_LEVEL, Skill::MAX_LEVEL));
         
         self.send("uciok");
     }
 
     fn cmd_debug(&mut self, on: bool) {
         self.debug = on;
+        self.listener.set_debug(on);
     }
 
     fn cmd_isready(&self) {
@@ -189,10 +339,31 @@ impl UciHandler {
 
     fn cmd_setoption(&mut self, name: &str, value: Option<&str>) {
         match name.to_lowercase().as_str() {
+            "hash" => {
+                if let Some(v) = value {
+                    if let Ok(mb) = v.parse::<usize>() {
+                        self.shared = {
+                            let mut searcher = self.searcher();
+                            searcher.set_hash_size(mb);
+                            Arc::clone(&searcher.shared)
+                        };
+                    }
+                }
+            }
+            "clear hash" => {
+                self.searcher().clear_hash();
+            }
             "threads" => {
                 if let Some(v) = value {
                     if let Ok(n) = v.parse::<usize>() {
-                        self.searcher.set_threads(n);
+                        self.searcher().set_threads(n);
+                    }
+                }
+            }
+            "multipv" => {
+                if let Some(v) = value {
+                    if let Ok(n) = v.parse::<usize>() {
+                        self.searcher().set_multi_pv(n);
                     }
                 }
             }
@@ -203,6 +374,9 @@ impl UciHandler {
                     }
                 }
             }
+            "ponder" => {
+                // Only tells the GUI it may send `go ponder`; nothing to configure
+            }
             "ownbook" => {
                 if let Some(v) = value {
                     self.use_own_book = v.to_lowercase() == "true";
@@ -227,6 +401,114 @@ impl UciHandler {
                     }
                 }
             }
+            "bookdepth" => {
+                if let Some(v) = value {
+                    if let Ok(moves) = v.parse::<u32>() {
+                        self.book_depth = moves.min(DEFAULT_BOOK_DEPTH);
+                    }
+                }
+            }
+            "bookbestmove" => {
+                if let Some(v) = value {
+                    self.book_best_move = v.to_lowercase() == "true";
+                }
+            }
+            "bookminweight" => {
+                if let Some(v) = value {
+                    if let Ok(weight) = v.parse::<u16>() {
+                        self.book_min_weight = weight.max(1);
+                    }
+                }
+            }
+            "bookseed" => {
+                if let Some(v) = value {
+                    if let Ok(seed) = v.parse::<u64>() {
+                        self.book_rng = if seed == 0 { Prng::from_time() } else { Prng::new(seed) };
+                    }
+                }
+            }
+            "bookcheckdepth" => {
+                if let Some(v) = value {
+                    if let Ok(depth) = v.parse::<i32>() {
+                        self.book_check_depth = depth.clamp(0, 20);
+                    }
+                }
+            }
+            "bookcheckmargin" => {
+                if let Some(v) = value {
+                    if let Ok(cp) = v.parse::<i32>() {
+                        self.book_check_margin = cp.clamp(0, 1000);
+                    }
+                }
+            }
+            "booklearning" => {
+                if let Some(v) = value {
+                    self.book_learning = v.to_lowercase() == "true";
+                }
+            }
+            "booklearningguess" => {
+                if let Some(v) = value {
+                    self.book_learning_guess = v.to_lowercase() == "true";
+                }
+            }
+            "uci_chess960" => {
+                if let Some(v) = value {
+                    self.chess960 = v.to_lowercase() == "true";
+                    self.listener.set_chess960(self.chess960);
+                }
+            }
+            "showsanpv" => {
+                if let Some(v) = value {
+                    self.listener.set_san_pv(v.to_lowercase() == "true");
+                }
+            }
+            "syzygypath" => {
+                let path = value.unwrap_or("").trim();
+                let tablebases = if path.is_empty() || path == "<empty>" {
+                    None
+                } else {
+                    let tb = Tablebases::load(path);
+                    if tb.is_empty() {
+                        println!("info string No Syzygy tablebases found in {}", path);
+                        None
+                    } else {
+                        println!("info string Found {} Syzygy tablebases (up to {} pieces)", tb.len(), tb.max_pieces());
+                        Some(Arc::new(tb))
+                    }
+                };
+                self.searcher().set_tablebases(tablebases);
+            }
+            "syzygyprobedepth" => {
+                if let Some(v) = value {
+                    if let Ok(depth) = v.parse::<i32>() {
+                        self.searcher().set_tb_probe_depth(depth.clamp(1, 100));
+                    }
+                }
+            }
+            "syzygy50moverule" => {
+                if let Some(v) = value {
+                    self.searcher().set_tb_use_rule50(v.to_lowercase() == "true");
+                }
+            }
+            "contempt" => {
+                if let Some(v) = value {
+                    if let Ok(cp) = v.parse::<i32>() {
+                        self.searcher().set_contempt(cp.clamp(-100, 100));
+                    }
+                }
+            }
+            "analysiscontempt" => {
+                if let Some(v) = value {
+                    self.searcher().set_analysis_contempt(v.to_lowercase() == "true");
+                }
+            }
+            "skill level" => {
+                if let Some(v) = value {
+                    if let Ok(level) = v.parse::<i32>() {
+                        self.searcher().set_skill(Skill::from_level(level));
+                    }
+                }
+            }
             _ => {
                 if self.debug {
                     eprintln!("Unknown option: {}", name);
@@ -236,95 +518,147 @@ impl UciHandler {
     }
 
     fn cmd_ucinewgame(&mut self) {
-        // Preserve NNUE model before resetting
-        let nnue_model = self.searcher.nnue.take();
-        
+        // Learn from the game that just ended before its search state goes
+        self.finish_book_learning(None);
+
+        // Clear the TT and heuristics in place (keeps Hash, Threads and NNUE)
+        self.searcher().new_game();
         self.board = Board::default();
-        self.searcher = Searcher::new();
-        
-        // Restore NNUE model
-        self.searcher.nnue = nnue_model;
+        self.halfmove_clock = 0;
+        self.game_ply = 0;
+        self.game_result = None;
     }
 
     fn cmd_position(&mut self, fen: Option<&str>, moves: &[String]) {
         // Set up the position
         self.board = match fen {
-            Some(f) => Board::from_fen(f).unwrap_or_default(),
+            Some(f) => Board::from_fen(&standard_castling_fen(f)).unwrap_or_default(),
             None => Board::default(),
         };
+        self.halfmove_clock = fen.map_or(0, fen_halfmove_clock);
+        self.game_ply = fen.map_or(0, fen_game_ply);
 
-        // Track position hashes for repetition detection
-        let mut history: Vec<u64> = Vec::with_capacity(moves.len() + 1);
-        history.push(self.board.hash());
+        // Track the positions before the current one for repetition detection
+        let mut history: Vec<u64> = Vec::with_capacity(moves.len());
 
         // Apply moves
         for move_str in moves {
             if let Some(m) = parse_move(&self.board, move_str) {
-                self.board = self.board.make_move_new(m);
+                self.halfmove_clock = if is_zeroing(&self.board, m) { 0 } else { self.halfmove_clock + 1 };
                 history.push(self.board.hash());
+                self.board = self.board.make_move_new(m);
+                self.game_ply += 1;
             } else if self.debug {
                 eprintln!("Invalid move: {}", move_str);
             }
         }
         
-        // Store history in searcher for repetition detection
-        self.searcher.set_position_with_history(self.board, history);
+        self.game_result = game_over_result(&self.board, self.halfmove_clock, &history);
+
+        // The searcher keeps the position (and its history) until the next one
+        let board = self.board;
+        let halfmove_clock = self.halfmove_clock;
+        let mut searcher = self.searcher();
+        searcher.set_position_with_history(board, history);
+        searcher.set_halfmove_clock(halfmove_clock);
+        searcher.set_castling_rooks(fen.map_or_else(CastlingRooks::default, CastlingRooks::from_fen));
     }
 
-    fn cmd_go(&mut self, params: SearchParams) {
-        // Try opening book first (unless infinite or analysis mode)
-        if self.use_own_book && !params.infinite && params.searchmoves.is_empty() {
-            if let Some(ref book) = self.book {
-                if let Some(book_move) = book.probe_move(&self.board) {
+    fn cmd_go(&mut self, mut params: SearchParams, searchmoves: &[String]) {
+        // The clock runs from `go`, book checks included
+        let started = Instant::now();
+        self.stop_search();
+
+        // Restrict the root to the requested moves. Illegal ones are reported,
+        // and a list with none legal never widens the search to all moves.
+        for move_str in searchmoves {
+            match parse_move(&self.board, move_str) {
+                Some(m) => params.searchmoves.push(m),
+                None => self.send(&format!("info string searchmoves: illegal move {}", move_str)),
+            }
+        }
+        if !searchmoves.is_empty() && params.searchmoves.is_empty() {
+            self.send("info string searchmoves: no legal move to search");
+            self.send("bestmove 0000");
+            return;
+        }
+
+        // Try opening book first (unless infinite, pondering or analysis mode)
+        let mut book_choices = Vec::new();
+        if self.use_own_book && !params.infinite && !params.ponder && searchmoves.is_empty() {
+            book_choices = self.book_choices();
+            if self.book_check_depth == 0 {
+                if let Some(&book_move) = book_choices.first() {
+                    if self.book_learning {
+                        lock(&self.book_line).record(&self.board, book_move);
+                    }
                     self.send(&format!("info string book move"));
-                    self.send(&format!("bestmove {}", format_move(book_move)));
+                    self.send(&format!("bestmove {}", format_move_960(&self.board, book_move, self.chess960)));
                     return;
                 }
             }
         }
 
+        self.searched_this_game = true;
+
         // Set up search limits with move overhead
         let limits = SearchLimits::from_params(&params)
             .with_move_overhead(self.move_overhead);
-        
-        // Set position and run search
-        self.searcher.set_position(self.board);
-        let result = self.searcher.search(limits);
-
-        // Send info
-        let stats = result.stats;
-        let pv_str: String = result.pv.iter()
-            .map(|m| format_move(*m))
-            .collect::<Vec<_>>()
-            .join(" ");
-
-        self.send(&format!(
-            "info depth {} seldepth {} score {} nodes {} nps {} time {} pv {}",
-            stats.depth.raw(),
-            stats.seldepth.raw(),
-            result.score,
-            stats.nodes,
-            stats.nps(),
-            stats.time_ms,
-            pv_str
-        ));
-
-        // Send best move
-        match result.best_move {
-            Some(m) => self.send(&format!("bestmove {}", format_move(m))),
-            None => self.send("bestmove 0000"),
-        }
+
+        // Book checks get at most half of the move's target time
+        let time = TimeManager::from_limits(&limits, self.board.turn());
+        let check_deadline = (!time.is_infinite())
+            .then(|| started + Duration::from_millis(time.soft_limit_ms() / 2));
+
+        // Clear any stale stop request before the search thread starts, so a
+        // `stop` or `ponderhit` that arrives right after `go` is never lost.
+        // A ponder search runs with the real clock limits but ignores them
+        // until `ponderhit`.
+        self.shared.stop.store(false, Ordering::Relaxed);
+        self.shared.ponder.store(params.ponder, Ordering::Relaxed);
+
+        let searcher = Arc::clone(&self.searcher);
+        let listener = Arc::clone(&self.listener);
+        let book_line = Arc::clone(&self.book_line);
+        let (board, learning, check_depth, margin) =
+            (self.board, self.book_learning, self.book_check_depth, self.book_check_margin);
+
+        // Search on a background thread; the listener reports `bestmove`
+        self.search_thread = Some(thread::spawn(move || {
+            // Searches the position (and game history) set by `position`
+            let mut searcher = searcher.lock().unwrap_or_else(|e| e.into_inner());
+
+            // Book moves to vet come first; the search only runs if all lose
+            if !book_choices.is_empty() {
+                searcher.set_listener(Arc::new(SilentListener));
+                let book_move = vet_book_moves(&mut searcher, &book_choices, check_depth, margin, check_deadline);
+                searcher.set_listener(listener.clone());
+
+                if let Some(m) = book_move {
+                    if learning {
+                        lock(&book_line).record(&board, m);
+                    }
+                    send("info string book move");
+                    listener.on_bestmove(&board, Some(m), None);
+                    return;
+                }
+            }
+            searcher.search(limits.with_time_used(started.elapsed().as_millis() as u64));
+        }));
     }
 
     fn cmd_stop(&mut self) {
-        self.searcher.stop();
+        self.stop_search();
     }
 
     fn cmd_ponderhit(&mut self) {
-        // TODO: Switch from pondering to normal search
+        // The opponent played the expected move: keep the search (and its
+        // elapsed time and TT contents) but start honouring the clock
+        self.shared.ponder.store(false, Ordering::Relaxed);
     }
 
     fn cmd_quit(&mut self) {
+        self.stop_search();
         self.quit = true;
     }
 
@@ -334,15 +668,330 @@ impl UciHandler {
         eprintln!("FEN: {}", self.board.to_fen());
         eprintln!("Side to move: {:?}", self.board.turn());
     }
+
+    fn cmd_bench(&mut self, depth: Option<i32>, threads: Option<usize>, hash: Option<usize>) {
+        // Non-standard: fixed-depth search over the bench positions with a
+        // fresh searcher, so engine options and game state don't matter
+        let model = self.searcher().nnue.clone();
+        let result = tools::run_bench(
+            depth.unwrap_or(tools::DEFAULT_BENCH_DEPTH),
+            threads.unwrap_or(1).max(1),
+            hash.unwrap_or(16).clamp(1, Searcher::MAX_HASH_MB),
+            model,
+        );
+
+        eprintln!();
+        eprintln!("===========================");
+        eprintln!("Total time (ms) : {}", result.time_ms);
+        eprintln!("Nodes searched  : {}", result.nodes);
+        eprintln!("Nodes/second    : {}", result.nps());
+    }
+
+    fn cmd_perft(&mut self, depth: u32) {
+        // Non-standard: node count per root move of the current position
+        let start = std::time::Instant::now();
+        let mut total = 0;
+        for (m, nodes) in tools::divide(&self.board, depth) {
+            self.send(&format!("{}: {}", format_move_960(&self.board, m, self.chess960), nodes));
+            total += nodes;
+        }
+        let ms = start.elapsed().as_millis() as u64;
+
+        self.send("");
+        self.send(&format!("Nodes searched: {}", total));
+        self.send(&format!("Time (ms): {}  nps: {}", ms, total * 1000 / ms.max(1)));
+    }
+
+    fn cmd_testsuite(&mut self, path: &str, movetime: Option<u64>) {
+        // Non-standard: search each EPD position with the current Threads,
+        // Hash and NNUE settings and report which ones were solved
+        let (threads, hash, model) = {
+            let searcher = self.searcher();
+            (searcher.threads(), searcher.hash_size(), searcher.nnue.clone())
+        };
+
+        let report = |r: &tools::PositionResult| {
+            let id = r.id.as_deref().unwrap_or("-");
+            let found = r.found.as_deref().unwrap_or("(none)");
+            let line = match (&r.error, r.time_to_solve_ms) {
+                (Some(e), _) => format!("{:>4} {} error: {}", r.index, id, e),
+                (None, Some(ms)) => format!("{:>4} {} solved {} in {} ms", r.index, id, found, ms),
+                (None, None) => format!("{:>4} {} failed, played {}", r.index, id, found),
+            };
+            send(&format!("info string {}", line));
+        };
+
+        match tools::run_testsuite(Path::new(path), movetime.unwrap_or(1000), threads, hash, model, report) {
+            Ok(summary) => {
+                let searched = summary.positions - summary.errors;
+                self.send(&format!(
+                    "info string solved {}/{} ({} errors), avg time to solution {} ms, nodes {}",
+                    summary.solved,
+                    searched,
+                    summary.errors,
+                    summary.solve_time_ms / summary.solved.max(1) as u64,
+                    summary.nodes
+                ));
+            }
+            Err(e) => self.send(&format!("info string testsuite {} not loaded: {}", path, e)),
+        }
+    }
+
+    /// Book moves for the current position under the Book* options, in the
+    /// order to try them: the weighted pick (or heaviest move) first, then,
+    /// when `BookCheckDepth` vets them, the next picks among the rest.
+    /// Empty once the game is past `BookDepth`.
+    fn book_choices(&mut self) -> Vec<Move> {
+        let mut choices = Vec::new();
+        if self.game_ply / 2 >= self.book_depth {
+            return choices;
+        }
+        let Some(book) = self.book.as_ref() else { return choices };
+        let mut moves = book.candidates(&self.board, self.book_min_weight);
+
+        loop {
+            let pick = if self.book_best_move {
+                PolyglotBook::pick_best(&moves)
+            } else {
+                PolyglotBook::pick_weighted(&moves, &mut self.book_rng)
+            };
+            let Some(m) = pick else { break };
+            choices.push(m);
+            if self.book_check_depth == 0 {
+                break;
+            }
+            moves.retain(|&(n, _)| n != m);
+        }
+        choices
+    }
+
+    /// Reweight the book moves played this game by its result and write
+    /// the book back (BookLearning). The result comes from the `result`
+    /// command or from a final position ended by mate or a draw rule; only
+    /// with BookLearningGuess does the last search score stand in for it.
+    fn finish_book_learning(&mut self, result: Option<GameResult>) {
+        // The search thread may still record a book move
+        self.stop_search();
+        let BookLine { moves: line, side } = std::mem::take(&mut *lock(&self.book_line));
+        let searched = std::mem::replace(&mut self.searched_this_game, false);
+        let last_score = self.listener.take_last_score();
+        if !self.book_learning || line.is_empty() || self.book.is_none() {
+            return;
+        }
+
+        let outcome = match (result.or(self.game_result), side) {
+            (Some(result), Some(side)) => Outcome::from_result(result, side),
+            (None, _) if searched && self.book_learning_guess => last_score.map(Outcome::from_score),
+            _ => None,
+        };
+        let (Some(outcome), Some(book)) = (outcome, self.book.as_mut()) else {
+            return;
+        };
+
+        match book.learn(&line, outcome) {
+            Ok(updated) => send(&format!("info string book learning: {:?}, {} entries updated", outcome, updated)),
+            Err(e) => send(&format!("info string book learning: {} not written: {}", self.book_path, e)),
+        }
+    }
+
+    fn cmd_makebook(&mut self, pgn_path: &str, bin_path: &str, max_ply: Option<usize>, min_games: Option<u32>) {
+        // Non-standard: build a Polyglot book from the main lines of a PGN file
+        let mut builder = BookBuilder::new()
+            .with_max_ply(max_ply.unwrap_or(book::DEFAULT_MAX_PLY))
+            .with_min_games(min_games.unwrap_or(book::DEFAULT_MIN_GAMES));
+
+        let summary = match builder.add_pgn_file(pgn_path) {
+            Ok(summary) => summary,
+            Err(e) => {
+                self.send(&format!("info string makebook: {} not loaded: {}", pgn_path, e));
+                return;
+            }
+        };
+        match builder.write(bin_path) {
+            Ok(entries) => self.send(&format!(
+                "info string makebook: {} games ({} unfinished, {} errors), {} entries written to {}",
+                summary.added, summary.unfinished, summary.errors, entries, bin_path
+            )),
+            Err(e) => self.send(&format!("info string makebook: {} not written: {}", bin_path, e)),
+        }
+    }
+}
+
+/// Write a line to stdout and flush it (callable from the search thread)
+fn send(msg: &str) {
+    println!("{}", msg);
+    io::stdout().flush().ok();
+}
+
+/// Result of a game whose last position is `board`, if the rules ended
+/// it: checkmate, stalemate, the fifty-move rule, threefold repetition or
+/// insufficient material. `history` holds the earlier positions' hashes.
+fn game_over_result(board: &Board, halfmove_clock: u32, history: &[u64]) -> Option<GameResult> {
+    if board.generate_moves().is_empty() {
+        return Some(match (board.in_check(), board.turn()) {
+            (false, _) => GameResult::Draw,
+            (true, Color::White) => GameResult::BlackWins,
+            (true, Color::Black) => GameResult::WhiteWins,
+        });
+    }
+
+    let hash = board.hash();
+    let threefold = history.iter().filter(|&&h| h == hash).count() >= 2;
+    if halfmove_clock >= 100 || threefold || is_insufficient_material(board) {
+        return Some(GameResult::Draw);
+    }
+    None
+}
+
+/// Lock a mutex, ignoring poisoning
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// First of the book moves `choices` that a quick search (`BookCheckDepth`,
+/// capped at `BOOK_CHECK_NODES`) doesn't score below minus `margin`.
+/// After a `stop`, the move being vetted is played unchecked. The checks
+/// end by `deadline` (if the move is timed); moves left unchecked then go
+/// to the full search. Runs on the search thread.
+fn vet_book_moves(
+    searcher: &mut Searcher,
+    choices: &[Move],
+    depth: i32,
+    margin: i32,
+    deadline: Option<Instant>,
+) -> Option<Move> {
+    for &m in choices {
+        let mut limits = SearchLimits::depth(depth);
+        limits.nodes = Some(BOOK_CHECK_NODES);
+        limits.searchmoves = vec![m];
+        if let Some(deadline) = deadline {
+            let left = deadline.saturating_duration_since(Instant::now()).as_millis() as u64;
+            if left == 0 {
+                return None;
+            }
+            limits.movetime = Some(left);
+            limits.move_overhead = 0;
+        }
+
+        let result = searcher.search(limits);
+        if searcher.stop_requested() || result.score >= Score::cp(-margin) {
+            return Some(m);
+        }
+    }
+    None
+}
+
+/// Search listener that writes UCI `info` and `bestmove` lines
+#[derive(Debug, Default)]
+pub struct UciListener {
+    /// Write castling as king takes rook (UCI_Chess960)
+    chess960: AtomicBool,
+    /// Also send profiling counters as `info string` (debug on)
+    debug: AtomicBool,
+    /// Also send each PV in SAN as `info string` (ShowSanPv)
+    san_pv: AtomicBool,
+    /// Exact score of the first line last reported. Only real searches
+    /// report here; book checks run with a `SilentListener`.
+    last_score: Mutex<Option<Score>>,
+}
+
+impl UciListener {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_chess960(&self, chess960: bool) {
+        self.chess960.store(chess960, Ordering::Relaxed);
+    }
+
+    pub fn set_debug(&self, debug: bool) {
+        self.debug.store(debug, Ordering::Relaxed);
+    }
+
+    pub fn set_san_pv(&self, san_pv: bool) {
+        self.san_pv.store(san_pv, Ordering::Relaxed);
+    }
+
+    /// Take the score of the last reported search (for book learning)
+    pub fn take_last_score(&self) -> Option<Score> {
+        lock(&self.last_score).take()
+    }
+
+    fn chess960(&self) -> bool {
+        self.chess960.load(Ordering::Relaxed)
+    }
+}
+
+impl SearchListener for UciListener {
+    fn on_pv(&self, board: &Board, info: &PvInfo) {
+        if info.multipv == 1 && info.bound.is_none() {
+            *lock(&self.last_score) = Some(info.score);
+        }
+
+        send(&InfoBuilder::new()
+            .depth(info.depth)
+            .seldepth(info.seldepth)
+            .multipv(info.multipv)
+            .score_bound(info.score, info.bound)
+            .nodes(info.nodes)
+            .nps(info.nps)
+            .time(info.time_ms)
+            .hashfull(info.hashfull)
+            .tbhits(info.tb_hits)
+            .pv(board, &info.pv, self.chess960())
+            .build());
+
+        // GUIs need UCI moves in `pv`; the SAN line is for humans reading the log
+        if self.san_pv.load(Ordering::Relaxed) && !info.pv.is_empty() {
+            send(&format!("info string multipv {} pv {}", info.multipv, line_to_san(board, &info.pv)));
+        }
+    }
+
+    fn on_iteration(&self, _board: &Board, stats: &SearchStats) {
+        if self.debug.load(Ordering::Relaxed) {
+            if let Some(summary) = stats.profile_summary() {
+                send(&format!("info string {}", summary));
+            }
+        }
+    }
+
+    fn on_currmove(&self, board: &Board, info: &CurrMoveInfo) {
+        send(&InfoBuilder::new()
+            .depth(info.depth)
+            .currmove(format_move_960(board, info.mv, self.chess960()))
+            .currmovenumber(info.number)
+            .build());
+    }
+
+    fn on_progress(&self, info: &ProgressInfo) {
+        send(&InfoBuilder::new()
+            .nodes(info.nodes)
+            .nps(info.nps)
+            .hashfull(info.hashfull)
+            .tbhits(info.tb_hits)
+            .time(info.time_ms)
+            .build());
+    }
+
+    fn on_bestmove(&self, board: &Board, best: Option<Move>, ponder: Option<Move>) {
+        let chess960 = self.chess960();
+        match (best, ponder) {
+            (Some(m), Some(p)) => send(&format!(
+                "bestmove {} ponder {}",
+                format_move_960(board, m, chess960),
+                format_move_960(&board.make_move_new(m), p, chess960),
+            )),
+            (Some(m), None) => send(&format!("bestmove {}", format_move_960(board, m, chess960))),
+            (None, _) => send("bestmove 0000"),
+        }
+    }
 }
 
 /// Info message builder for search output
-#[allow(dead_code)]
 pub struct InfoBuilder {
     parts: Vec<String>,
 }
 
-#[allow(dead_code)]
 impl InfoBuilder {
     pub fn new() -> Self {
         Self { parts: Vec::new() }
@@ -358,11 +1007,27 @@ impl InfoBuilder {
         self
     }
 
+    pub fn multipv(mut self, index: usize) -> Self {
+        self.parts.push(format!("multipv {}", index));
+        self
+    }
+
     pub fn score(mut self, s: Score) -> Self {
         self.parts.push(format!("score {}", s));
         self
     }
 
+    /// Score that may only be a bound (aspiration fail-high/low)
+    pub fn score_bound(mut self, s: Score, bound: Option<BoundType>) -> Self {
+        let suffix = match bound {
+            Some(BoundType::LowerBound) => " lowerbound",
+            Some(BoundType::UpperBound) => " upperbound",
+            _ => "",
+        };
+        self.parts.push(format!("score {}{}", s, suffix));
+        self
+    }
+
     pub fn nodes(mut self, n: u64) -> Self {
         self.parts.push(format!("nodes {}", n));
         self
@@ -378,10 +1043,20 @@ impl InfoBuilder {
         self
     }
 
-    pub fn pv(mut self, moves: &[Move]) -> Self {
+    pub fn currmove(mut self, m: String) -> Self {
+        self.parts.push(format!("currmove {}", m));
+        self
+    }
+
+    pub fn currmovenumber(mut self, n: usize) -> Self {
+        self.parts.push(format!("currmovenumber {}", n));
+        self
+    }
+
+    /// Principal variation played from `board`
+    pub fn pv(mut self, board: &Board, moves: &[Move], chess960: bool) -> Self {
         if !moves.is_empty() {
-            let pv_str: Vec<String> = moves.iter().map(|m| format_move(*m)).collect();
-            self.parts.push(format!("pv {}", pv_str.join(" ")));
+            self.parts.push(format!("pv {}", castling::line_to_uci(board, moves, chess960)));
         }
         self
     }
@@ -391,6 +1066,11 @@ impl InfoBuilder {
         self
     }
 
+    pub fn tbhits(mut self, n: u64) -> Self {
+        self.parts.push(format!("tbhits {}", n));
+        self
+    }
+
     pub fn build(self) -> String {
         format!("info {}", self.parts.join(" "))
     }
@@ -401,3 +1081,72 @@ impl Default for InfoBuilder {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::{encode_move, polyglot_hash, BookEntry};
+    use std::sync::mpsc;
+
+    /// Run `commands` on a fresh handler off the test thread, so a command
+    /// that blocks fails the test instead of hanging it
+    fn run_commands(commands: &'static [&'static str]) -> Option<UciHandler> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut handler = UciHandler::new();
+            for command in commands {
+                handler.handle_input(command);
+            }
+            tx.send(handler).ok();
+        });
+        rx.recv_timeout(Duration::from_secs(30)).ok()
+    }
+
+    #[test]
+    fn test_position_during_infinite_search() {
+        let handler = run_commands(&[
+            "position startpos",
+            "go infinite",
+            "position startpos moves e2e4",
+            "stop",
+        ])
+        .expect("position blocked on the running search");
+        assert!(handler.search_thread.is_none());
+
+        let e4 = Board::default().make_move_new(parse_move(&Board::default(), "e2e4").unwrap());
+        assert_eq!(handler.board.hash(), e4.hash());
+    }
+
+    #[test]
+    fn test_illegal_searchmoves_search_nothing() {
+        let handler = run_commands(&["position startpos", "go depth 5 searchmoves e7e5 e2e5"])
+            .expect("go blocked");
+        assert!(handler.search_thread.is_none());
+    }
+    #[test]
+    fn test_book_checks_count_against_movetime() {
+        // Every legal move of the start position is a book move
+        let board = Board::default();
+        let key = polyglot_hash(&board);
+        let bytes: Vec<u8> = board.generate_moves().iter()
+            .flat_map(|m| BookEntry { key, raw_move: encode_move(&board, m), weight: 1, learn: 0 }.to_bytes())
+            .collect();
+        let path = std::env::temp_dir().join(format!("book_check_time_{}.bin", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+
+        let mut handler = UciHandler::new();
+        handler.handle_input(&format!("setoption name BookPath value {}", path.display()));
+        handler.handle_input("setoption name BookCheckDepth value 20");
+        handler.handle_input("setoption name BookCheckMargin value 0");
+        handler.handle_input("position startpos");
+
+        let start = Instant::now();
+        handler.handle_input("go movetime 300");
+        handler.wait_for_search();
+        let elapsed = start.elapsed();
+        std::fs::remove_file(&path).ok();
+
+        assert!(handler.book.is_some());
+        assert!(elapsed < Duration::from_millis(400), "took {:?}", elapsed);
+    }
+}
@@ -5,10 +5,13 @@
 
 mod parser;
 mod handler;
+mod san;
 
-pub use handler::UciHandler;
+pub use handler::{UciHandler, UciListener, InfoBuilder};
+pub use san::{parse_san, to_san, line_to_san};
 
 use crate::types::{Board, Move, Depth, Piece};
+use crate::types::castling;
 use movegen::Square;
 
 /// UCI engine identification
@@ -93,6 +96,13 @@ pub fn parse_move(board: &Board, move_str: &str) -> Option<Move> {
         None
     };
 
+    // Chess960 GUIs write castling as the king taking its own rook
+    if promo_piece.is_none() {
+        if let Some(m) = castling::find_castling_move(board, from, to) {
+            return Some(m);
+        }
+    }
+
     // Find the matching legal move
     let moves = board.generate_moves();
     for m in moves.iter() {
@@ -115,3 +125,159 @@ pub fn parse_move(board: &Board, move_str: &str) -> Option<Move> {
 pub fn format_move(m: Move) -> String {
     m.to_uci()
 }
+
+/// Format a move played from `board`, writing castling as king takes rook
+/// in Chess960 mode (e.g. "e1h1")
+pub fn format_move_960(board: &Board, m: Move, chess960: bool) -> String {
+    castling::move_to_uci(board, m, chess960)
+}
+
+/// Fifty-move counter (5th field) of a FEN string, 0 if missing
+pub fn fen_halfmove_clock(fen: &str) -> u32 {
+    fen.split_whitespace()
+        .nth(4)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Plies played before a FEN position, from its side to move and full
+/// move number (6th field, 1 if missing)
+pub fn fen_game_ply(fen: &str) -> u32 {
+    let mut fields = fen.split_whitespace().skip(1);
+    let black = fields.next() == Some("b");
+    let fullmove: u32 = fields.nth(3).and_then(|n| n.parse().ok()).unwrap_or(1);
+    fullmove.max(1).saturating_sub(1) * 2 + u32::from(black)
+}
+
+/// Rewrite the castling field of a Shredder-FEN or X-FEN string to the
+/// standard `KQkq` form.
+///
+/// Shredder-FEN names the castling rooks by file (`HAha`); X-FEN does so
+/// only when `K`/`Q` would be ambiguous. A file right of the king means
+/// kingside, left of it queenside. `movegen` only keeps these four flags;
+/// `CastlingRooks::from_fen` keeps which rooks hold them.
+pub fn standard_castling_fen(fen: &str) -> String {
+    let mut fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 3 || fields[2] == "-" {
+        return fen.to_string();
+    }
+
+    // File of a king on its back rank
+    let ranks: Vec<&str> = fields[0].split('/').collect();
+    let king_file = |rank: Option<&&str>, king: char| -> Option<u8> {
+        let mut file = 0u8;
+        for c in rank?.chars() {
+            if c == king {
+                return Some(file);
+            }
+            file += c.to_digit(10).map_or(1, |n| n as u8);
+        }
+        None
+    };
+    let white_king = king_file(ranks.last(), 'K');
+    let black_king = king_file(ranks.first(), 'k');
+
+    let mut rights = Vec::new();
+    for c in fields[2].chars() {
+        let right = match (c, white_king, black_king) {
+            ('K' | 'Q' | 'k' | 'q', _, _) => c,
+            ('A'..='H', Some(king), _) => if (c as u8 - b'A') > king { 'K' } else { 'Q' },
+            ('a'..='h', _, Some(king)) => if (c as u8 - b'a') > king { 'k' } else { 'q' },
+            _ => continue,
+        };
+        rights.push(right);
+    }
+
+    let castling: String = "KQkq".chars().filter(|c| rights.contains(c)).collect();
+    let castling = if castling.is_empty() { "-".to_string() } else { castling };
+    fields[2] = &castling;
+    fields.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standard_castling_fen() {
+        // Shredder-FEN for the standard start position
+        assert_eq!(
+            standard_castling_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+
+        // Chess960 position with the king on b1/b8
+        assert_eq!(
+            standard_castling_fen("rkrbbnnq/pppppppp/8/8/8/8/PPPPPPPP/RKRBBNNQ w CAca - 0 1"),
+            "rkrbbnnq/pppppppp/8/8/8/8/PPPPPPPP/RKRBBNNQ w KQkq - 0 1"
+        );
+
+        // Partial rights and already standard fields are kept
+        assert_eq!(
+            standard_castling_fen("rkrbbnnq/pppppppp/8/8/8/8/PPPPPPPP/RKRBBNNQ w Ca - 0 1"),
+            "rkrbbnnq/pppppppp/8/8/8/8/PPPPPPPP/RKRBBNNQ w Kq - 0 1"
+        );
+        assert_eq!(standard_castling_fen("8/8/8/8/8/8/8/K6k w - - 0 1"), "8/8/8/8/8/8/8/K6k w - - 0 1");
+        assert_eq!(
+            standard_castling_fen("r3k2r/8/8/8/8/8/8/R3K2R b Qk - 3 20"),
+            "r3k2r/8/8/8/8/8/8/R3K2R b Qk - 3 20"
+        );
+    }
+
+    /// Board of a FEN as the `position` command sets it up
+    fn board_960(fen: &str) -> Board {
+        Board::from_fen(&standard_castling_fen(fen)).unwrap()
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        use crate::types::castling::CastlingRooks;
+        use crate::types::Color;
+
+        // Shredder-FEN and X-FEN give the same position and rooks
+        for fen in ["rk4r1/8/8/8/8/8/8/RK4R1 w GAga - 0 1", "rk4r1/8/8/8/8/8/8/RK4R1 w KQkq - 0 1"] {
+            let board = board_960(fen);
+            let rooks = CastlingRooks::from_fen(fen);
+
+            let kingside = parse_move(&board, "b1g1").unwrap();
+            assert_eq!(parse_san(&board, "O-O"), Some(kingside), "{}", fen);
+            assert!(rooks.allows(&board, kingside), "{}", fen);
+            let after = board.make_move_new(kingside);
+            assert_eq!(after.piece_at(Square::from_algebraic("g1").unwrap()), Some((Piece::King, Color::White)));
+            assert_eq!(after.piece_at(Square::from_algebraic("f1").unwrap()), Some((Piece::Rook, Color::White)));
+            assert_eq!(after.piece_at(Square::from_algebraic("a1").unwrap()), Some((Piece::Rook, Color::White)));
+
+            let queenside = parse_move(&board, "b1a1").unwrap();
+            assert_eq!(parse_san(&board, "O-O-O"), Some(queenside), "{}", fen);
+            let after = board.make_move_new(queenside);
+            assert_eq!(after.piece_at(Square::from_algebraic("c1").unwrap()), Some((Piece::King, Color::White)));
+            assert_eq!(after.piece_at(Square::from_algebraic("d1").unwrap()), Some((Piece::Rook, Color::White)));
+            assert_eq!(after.piece_at(Square::from_algebraic("g1").unwrap()), Some((Piece::Rook, Color::White)));
+        }
+
+        // Two rooks on the queenside: only the one named in the FEN castles
+        let inner = "4k3/8/8/8/8/8/8/RR2K3 w B - 0 1";
+        let board = board_960(inner);
+        let castle = parse_move(&board, "e1b1").unwrap();
+        assert!(CastlingRooks::from_fen(inner).allows(&board, castle));
+        let after = board.make_move_new(castle);
+        assert_eq!(after.piece_at(Square::from_algebraic("c1").unwrap()), Some((Piece::King, Color::White)));
+        assert_eq!(after.piece_at(Square::from_algebraic("d1").unwrap()), Some((Piece::Rook, Color::White)));
+        assert_eq!(after.piece_at(Square::from_algebraic("a1").unwrap()), Some((Piece::Rook, Color::White)));
+
+        // With the right on the a1 rook, castling is blocked by the b1 rook
+        let outer = "4k3/8/8/8/8/8/8/RR2K3 w A - 0 1";
+        let board = board_960(outer);
+        let rooks = CastlingRooks::from_fen(outer);
+        assert!(board.generate_moves().iter()
+            .filter(|&m| castling::is_castling(m))
+            .all(|m| !rooks.allows(&board, m)));
+    }
+
+    #[test]
+    fn test_fen_game_ply() {
+        assert_eq!(fen_game_ply("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"), 0);
+        assert_eq!(fen_game_ply("r3k2r/8/8/8/8/8/8/R3K2R b Qk - 3 20"), 39);
+        assert_eq!(fen_game_ply("8/8/8/8/8/8/8/K6k b - -"), 1);
+    }
+}
@@ -1,6 +1,7 @@
 //! UCI command parser.
 
 use super::SearchParams;
+use crate::pgn::GameResult;
 use crate::types::Depth;
 
 /// Parsed UCI command
@@ -21,7 +22,10 @@ pub enum UciCommand {
     /// "position startpos/fen [moves ...]"
     Position { fen: Option<String>, moves: Vec<String> },
     /// "go ..." - Start searching
-    Go(SearchParams),
+    ///
+    /// `searchmoves` holds the raw move strings; they can only be parsed
+    /// once the handler has the board they apply to.
+    Go { params: SearchParams, searchmoves: Vec<String> },
     /// "stop" - Stop searching
     Stop,
     /// "ponderhit" - Opponent played expected move
@@ -30,6 +34,22 @@ pub enum UciCommand {
     Quit,
     /// "d" - Debug: display board (non-standard but common)
     Display,
+    /// "bench [depth] [threads] [hash]" - Search the bench positions and
+    /// report total nodes and speed (non-standard)
+    Bench { depth: Option<i32>, threads: Option<usize>, hash: Option<usize> },
+    /// "perft N" or "go perft N" - Count move tree leaves per root move
+    /// (non-standard)
+    Perft(u32),
+    /// "testsuite <file.epd> [movetime <ms>]" - Run an EPD test suite; the
+    /// path may contain spaces (non-standard)
+    TestSuite { path: String, movetime: Option<u64> },
+    /// "makebook <games.pgn> <book.bin> [maxply <n>] [mingames <n>]" - Build
+    /// a Polyglot book from a PGN file. Paths may contain spaces; the PGN
+    /// path then has to end in `.pgn` (non-standard)
+    MakeBook { pgn: String, book: String, max_ply: Option<usize>, min_games: Option<u32> },
+    /// "result <1-0|0-1|1/2-1/2>" - Result of the game just played, for
+    /// book learning (non-standard)
+    Result(GameResult),
     /// Unknown command
     Unknown(String),
 }
@@ -55,6 +75,18 @@ pub fn parse_command(input: &str) -> UciCommand {
         Some("ponderhit") => UciCommand::PonderHit,
         Some("quit") => UciCommand::Quit,
         Some("d") => UciCommand::Display,
+        Some("perft") => parse_perft(&mut parts),
+        Some("testsuite") => parse_testsuite(&mut parts),
+        Some("makebook") => parse_makebook(&mut parts),
+        Some("result") => match parts.next().and_then(GameResult::parse) {
+            Some(result) => UciCommand::Result(result),
+            None => UciCommand::Unknown(input.to_string()),
+        },
+        Some("bench") => UciCommand::Bench {
+            depth: parts.next().and_then(|t| t.parse().ok()),
+            threads: parts.next().and_then(|t| t.parse().ok()),
+            hash: parts.next().and_then(|t| t.parse().ok()),
+        },
         _ => UciCommand::Unknown(input.to_string()),
     }
 }
@@ -134,10 +166,80 @@ fn parse_position<'a>(parts: &mut impl Iterator<Item = &'a str>) -> UciCommand {
     UciCommand::Position { fen, moves }
 }
 
+fn parse_perft<'a>(parts: &mut impl Iterator<Item = &'a str>) -> UciCommand {
+    match parts.next().and_then(|t| t.parse().ok()) {
+        Some(depth) => UciCommand::Perft(depth),
+        None => UciCommand::Unknown("perft without a depth".to_string()),
+    }
+}
+
+/// Tokens up to the first of `keywords`, which is left in `parts`
+fn take_path<'a>(parts: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>, keywords: &[&str]) -> Vec<&'a str> {
+    let mut path = Vec::new();
+    while let Some(token) = parts.next_if(|t| !keywords.contains(t)) {
+        path.push(token);
+    }
+    path
+}
+
+fn parse_testsuite<'a>(parts: &mut impl Iterator<Item = &'a str>) -> UciCommand {
+    let mut parts = parts.peekable();
+    let path = take_path(&mut parts, &["movetime"]);
+    if path.is_empty() {
+        return UciCommand::Unknown("testsuite without a file".to_string());
+    }
+
+    let mut movetime = None;
+    while let Some(token) = parts.next() {
+        if token == "movetime" {
+            movetime = parts.next().and_then(|t| t.parse().ok());
+        }
+    }
+
+    UciCommand::TestSuite { path: path.join(" "), movetime }
+}
+
+fn parse_makebook<'a>(parts: &mut impl Iterator<Item = &'a str>) -> UciCommand {
+    let mut parts = parts.peekable();
+    let paths = take_path(&mut parts, &["maxply", "mingames"]);
+
+    // The PGN path ends at its `.pgn` extension (or after one token without one)
+    let pgn_end = paths.iter()
+        .position(|t| t.to_lowercase().ends_with(".pgn"))
+        .map_or(1, |i| i + 1);
+    if paths.len() <= pgn_end {
+        return UciCommand::Unknown("makebook needs a PGN file and a book file".to_string());
+    }
+    let (pgn, book) = (paths[..pgn_end].join(" "), paths[pgn_end..].join(" "));
+
+    let mut max_ply = None;
+    let mut min_games = None;
+    while let Some(token) = parts.next() {
+        match token {
+            "maxply" => max_ply = parts.next().and_then(|t| t.parse().ok()),
+            "mingames" => min_games = parts.next().and_then(|t| t.parse().ok()),
+            _ => {}
+        }
+    }
+
+    UciCommand::MakeBook { pgn, book, max_ply, min_games }
+}
+
+/// Keywords of the "go" command (used to find the end of a searchmoves list)
+const GO_KEYWORDS: [&str; 12] = [
+    "searchmoves", "ponder", "wtime", "btime", "winc", "binc",
+    "movestogo", "depth", "nodes", "mate", "movetime", "infinite",
+];
+
 fn parse_go<'a>(parts: &mut impl Iterator<Item = &'a str>) -> UciCommand {
     let mut params = SearchParams::new();
+    let mut searchmoves = Vec::new();
     
     let tokens: Vec<&str> = parts.collect();
+    if tokens.first() == Some(&"perft") {
+        return parse_perft(&mut tokens[1..].iter().copied());
+    }
+
     let mut i = 0;
 
     while i < tokens.len() {
@@ -201,12 +303,11 @@ fn parse_go<'a>(parts: &mut impl Iterator<Item = &'a str>) -> UciCommand {
                 }
             }
             "searchmoves" => {
-                // Remaining tokens are moves
-                // We'll parse them later when we have the board
-                i += 1;
-                while i < tokens.len() {
-                    // Store as strings for now, will be parsed with board context
+                // Moves run until the next keyword (or the end of the line)
+                // Store as strings for now, will be parsed with board context
+                while i + 1 < tokens.len() && !GO_KEYWORDS.contains(&tokens[i + 1]) {
                     i += 1;
+                    searchmoves.push(tokens[i].to_string());
                 }
             }
             _ => {}
@@ -214,5 +315,112 @@ fn parse_go<'a>(parts: &mut impl Iterator<Item = &'a str>) -> UciCommand {
         i += 1;
     }
 
-    UciCommand::Go(params)
+    UciCommand::Go { params, searchmoves }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_go_searchmoves() {
+        match parse_command("go searchmoves e2e4 d2d4 depth 10") {
+            UciCommand::Go { params, searchmoves } => {
+                assert_eq!(searchmoves, vec!["e2e4", "d2d4"]);
+                assert_eq!(params.depth, Some(Depth::new(10)));
+            }
+            other => panic!("Expected go command, got {:?}", other),
+        }
+
+        match parse_command("go infinite searchmoves g1f3") {
+            UciCommand::Go { params, searchmoves } => {
+                assert_eq!(searchmoves, vec!["g1f3"]);
+                assert!(params.infinite);
+            }
+            other => panic!("Expected go command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_bench() {
+        match parse_command("bench 8 2") {
+            UciCommand::Bench { depth, threads, hash } => {
+                assert_eq!(depth, Some(8));
+                assert_eq!(threads, Some(2));
+                assert_eq!(hash, None);
+            }
+            other => panic!("Expected bench command, got {:?}", other),
+        }
+
+        assert!(matches!(
+            parse_command("bench"),
+            UciCommand::Bench { depth: None, threads: None, hash: None }
+        ));
+    }
+
+    #[test]
+    fn test_parse_perft() {
+        assert!(matches!(parse_command("go perft 5"), UciCommand::Perft(5)));
+        assert!(matches!(parse_command("perft 3"), UciCommand::Perft(3)));
+        assert!(matches!(parse_command("perft"), UciCommand::Unknown(_)));
+    }
+
+    #[test]
+    fn test_parse_testsuite() {
+        match parse_command("testsuite wac.epd movetime 500") {
+            UciCommand::TestSuite { path, movetime } => {
+                assert_eq!(path, "wac.epd");
+                assert_eq!(movetime, Some(500));
+            }
+            other => panic!("Expected testsuite command, got {:?}", other),
+        }
+        match parse_command("testsuite /home/me/My Suites/wac 2018.epd movetime 200") {
+            UciCommand::TestSuite { path, movetime } => {
+                assert_eq!(path, "/home/me/My Suites/wac 2018.epd");
+                assert_eq!(movetime, Some(200));
+            }
+            other => panic!("Expected testsuite command, got {:?}", other),
+        }
+        match parse_command("testsuite C:\\Suites\\Win At Chess.epd") {
+            UciCommand::TestSuite { path, movetime } => {
+                assert_eq!(path, "C:\\Suites\\Win At Chess.epd");
+                assert_eq!(movetime, None);
+            }
+            other => panic!("Expected testsuite command, got {:?}", other),
+        }
+        assert!(matches!(parse_command("testsuite"), UciCommand::Unknown(_)));
+        assert!(matches!(parse_command("testsuite movetime 500"), UciCommand::Unknown(_)));
+    }
+
+    #[test]
+    fn test_parse_makebook() {
+        match parse_command("makebook games.pgn book.bin maxply 24 mingames 5") {
+            UciCommand::MakeBook { pgn, book, max_ply, min_games } => {
+                assert_eq!(pgn, "games.pgn");
+                assert_eq!(book, "book.bin");
+                assert_eq!(max_ply, Some(24));
+                assert_eq!(min_games, Some(5));
+            }
+            other => panic!("Expected makebook command, got {:?}", other),
+        }
+        match parse_command("makebook my games.pgn opening book.bin mingames 2") {
+            UciCommand::MakeBook { pgn, book, max_ply, min_games } => {
+                assert_eq!(pgn, "my games.pgn");
+                assert_eq!(book, "opening book.bin");
+                assert_eq!(max_ply, None);
+                assert_eq!(min_games, Some(2));
+            }
+            other => panic!("Expected makebook command, got {:?}", other),
+        }
+        assert!(matches!(parse_command("makebook games.pgn"), UciCommand::Unknown(_)));
+        assert!(matches!(parse_command("makebook my games.pgn maxply 10"), UciCommand::Unknown(_)));
+    }
+
+    #[test]
+    fn test_parse_result() {
+        assert!(matches!(parse_command("result 0-1"), UciCommand::Result(GameResult::BlackWins)));
+        assert!(matches!(parse_command("result 1/2-1/2"), UciCommand::Result(GameResult::Draw)));
+        assert!(matches!(parse_command("result"), UciCommand::Unknown(_)));
+        assert!(matches!(parse_command("result 2-0"), UciCommand::Unknown(_)));
+    }
 }
@@ -0,0 +1,227 @@
+//! Standard Algebraic Notation (SAN) move parsing and formatting.
+//!
+//! SAN names a move by the moving piece and its destination ("Nf3",
+//! "exd5", "e8=Q", "O-O"), adding the origin file and/or rank only when
+//! another piece of the same kind could reach the same square.
+
+use crate::types::{Board, Move, MoveFlag, Piece, Square};
+use crate::types::castling::{is_castling, square_name};
+
+/// Piece named by a SAN piece letter
+fn piece_from_letter(c: char) -> Option<Piece> {
+    match c {
+        'N' => Some(Piece::Knight),
+        'B' => Some(Piece::Bishop),
+        'R' => Some(Piece::Rook),
+        'Q' => Some(Piece::Queen),
+        'K' => Some(Piece::King),
+        _ => None,
+    }
+}
+
+/// SAN piece letter (pawns have none)
+fn piece_letter(piece: Piece) -> &'static str {
+    match piece {
+        Piece::Pawn => "",
+        Piece::Knight => "N",
+        Piece::Bishop => "B",
+        Piece::Rook => "R",
+        Piece::Queen => "Q",
+        Piece::King => "K",
+    }
+}
+
+/// Format a legal move in SAN (e.g. "Nbd7", "exd5", "e8=Q+", "O-O#")
+pub fn to_san(board: &Board, m: Move) -> String {
+    let mut san = match m.flag() {
+        MoveFlag::KingCastle => "O-O".to_string(),
+        MoveFlag::QueenCastle => "O-O-O".to_string(),
+        _ => {
+            let piece = board.piece_at(m.from()).map_or(Piece::Pawn, |(p, _)| p);
+            let mut san = piece_letter(piece).to_string();
+            let from = square_name(m.from());
+
+            if piece == Piece::Pawn {
+                // Pawn captures name the origin file
+                if m.is_capture() {
+                    san.push_str(&from[..1]);
+                }
+            } else {
+                // Disambiguate from other pieces of the same kind reaching `to`
+                let rivals: Vec<Square> = board.generate_moves().iter()
+                    .filter(|o| o.to() == m.to() && o.from() != m.from() && !is_castling(*o))
+                    .filter(|o| board.piece_at(o.from()).map(|(p, _)| p) == Some(piece))
+                    .map(|o| o.from())
+                    .collect();
+                if !rivals.is_empty() {
+                    let same_file = rivals.iter().any(|sq| sq.file() == m.from().file());
+                    let same_rank = rivals.iter().any(|sq| sq.rank() == m.from().rank());
+                    if !same_file {
+                        san.push_str(&from[..1]);
+                    } else if !same_rank {
+                        san.push_str(&from[1..]);
+                    } else {
+                        san.push_str(&from);
+                    }
+                }
+            }
+
+            if m.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&square_name(m.to()));
+            if let Some(promo) = m.flag().promotion_piece() {
+                san.push('=');
+                san.push_str(piece_letter(promo));
+            }
+            san
+        }
+    };
+
+    let after = board.make_move_new(m);
+    if after.in_check() {
+        san.push(if after.generate_moves().is_empty() { '#' } else { '+' });
+    }
+    san
+}
+
+/// Format a line of moves played from `board` in SAN
+pub fn line_to_san(board: &Board, moves: &[Move]) -> String {
+    let mut board = *board;
+    let mut out = Vec::with_capacity(moves.len());
+    for &m in moves {
+        out.push(to_san(&board, m));
+        board = board.make_move_new(m);
+    }
+    out.join(" ")
+}
+
+/// Parse a SAN move (e.g. "Nbd7", "exd5", "e8=Q+", "O-O") into the
+/// matching legal move for the given board.
+///
+/// Check, mate and annotation suffixes are ignored. Returns `None` if the
+/// move is illegal, malformed or ambiguous.
+pub fn parse_san(board: &Board, san: &str) -> Option<Move> {
+    let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+
+    // Castling (also accept zeros, which some EPD files use)
+    let castle = match san {
+        "O-O" | "0-0" => Some(MoveFlag::KingCastle),
+        "O-O-O" | "0-0-0" => Some(MoveFlag::QueenCastle),
+        _ => None,
+    };
+    if let Some(flag) = castle {
+        return board.generate_moves().iter().find(|m| m.flag() == flag);
+    }
+
+    let mut chars: Vec<char> = san.chars().filter(|&c| c != 'x' && c != '-').collect();
+
+    // Moving piece: pawn moves have no piece letter
+    let piece = match chars.first().copied().and_then(piece_from_letter) {
+        Some(p) => {
+            chars.remove(0);
+            p
+        }
+        None => Piece::Pawn,
+    };
+
+    // Promotion piece, written "e8=Q" or "e8Q"
+    let mut promotion = None;
+    if piece == Piece::Pawn {
+        if let Some(p) = chars.last().copied().and_then(piece_from_letter) {
+            promotion = Some(p);
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+    }
+
+    // Destination square is the last two characters; anything before it
+    // disambiguates the origin by file and/or rank
+    if chars.len() < 2 {
+        return None;
+    }
+    let dest: String = chars[chars.len() - 2..].iter().collect();
+    let to = Square::from_algebraic(&dest)?;
+    let hint = &chars[..chars.len() - 2];
+
+    let mut from_file = None;
+    let mut from_rank = None;
+    for &c in hint {
+        match c {
+            'a'..='h' => from_file = Some(c as u8 - b'a'),
+            '1'..='8' => from_rank = Some(c as u8 - b'1'),
+            _ => return None,
+        }
+    }
+
+    let mut found = None;
+    for m in board.generate_moves().iter() {
+        if m.to() != to
+            || m.flag().promotion_piece() != promotion
+            || board.piece_at(m.from()).map(|(p, _)| p) != Some(piece)
+            || from_file.is_some_and(|f| m.from().file().index() != f)
+            || from_rank.is_some_and(|r| m.from().rank().index() != r)
+        {
+            continue;
+        }
+        // Castling is only written as O-O / O-O-O
+        if matches!(m.flag(), MoveFlag::KingCastle | MoveFlag::QueenCastle) {
+            continue;
+        }
+        if found.is_some() {
+            return None;
+        }
+        found = Some(m);
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san_moves(fen: &str) -> Vec<(Move, String)> {
+        let board = Board::from_fen(fen).unwrap();
+        board.generate_moves().iter().map(|m| (m, to_san(&board, m))).collect()
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            for (m, san) in san_moves(fen) {
+                assert_eq!(parse_san(&board, &san), Some(m), "{} in {}", san, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_san_notation() {
+        // Knights on b8 and f6 can both reach d7
+        let board = Board::from_fen("rn2k3/8/5n2/8/8/5Q2/8/R3K2R b KQq - 0 1").unwrap();
+        let find = |san: &str| parse_san(&board, san).map(|m| to_san(&board, m));
+        assert_eq!(find("Nbd7").as_deref(), Some("Nbd7"));
+        assert_eq!(find("Nfd7").as_deref(), Some("Nfd7"));
+        assert_eq!(find("Nd7"), None);
+
+        let board = Board::from_fen("4k3/P7/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let find = |san: &str| parse_san(&board, san).map(|m| to_san(&board, m));
+        assert_eq!(find("a8=Q").as_deref(), Some("a8=Q+"));
+        assert_eq!(find("a8N").as_deref(), Some("a8=N"));
+        assert_eq!(find("0-0-0").as_deref(), Some("O-O-O"));
+        assert_eq!(find("O-O").as_deref(), Some("O-O"));
+
+        // Back-rank mate
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        assert_eq!(parse_san(&board, "Ra8").map(|m| to_san(&board, m)).as_deref(), Some("Ra8#"));
+    }
+}